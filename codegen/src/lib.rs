@@ -1,7 +1,15 @@
 use proc_macro::TokenStream;
 
+mod node;
 mod task;
 
+/// 把异步函数声明为任务处理器
+///
+/// `#[task("send_email", name = "发送邮件")]` 生成与函数同名的结构体并实现 `TaskHandler`,
+/// `for_task` 返回注解中的任务类型。函数参数按参数名从任务数据中取出,
+/// 引用类型的参数(`&Task`)接收任务本身。
+///
+/// 生成的处理器在链接时自动登记，`Worker::with_discovered_handlers` 可以直接使用。
 #[proc_macro_attribute]
 pub fn task(args: TokenStream, input: TokenStream) -> TokenStream {
     // args对应的是注解的参数部分
@@ -9,8 +17,18 @@ pub fn task(args: TokenStream, input: TokenStream) -> TokenStream {
     task::with(args, input)
 }
 
+/// 为自定义节点生成 `NodeDefinition`，只需再实现 `NodeTrait::execute`
+///
+/// - `#[input(Utf8)]` / `#[output(Float64)]` 标记 `Option<RecordBatch>` 类型的端点字段，字段名即端点名
+/// - `#[params]` 标记参数字段，参数类型需实现 `JsonSchema` 和 `Serialize`，用于生成 data_schema
+/// - `#[ui(field = "widget")]` 写在参数字段上，为参数指定前端表单控件
+#[proc_macro_derive(WorkflowNode, attributes(input, output, params, ui))]
+pub fn workflow_node(input: TokenStream) -> TokenStream {
+    node::derive(input)
+}
+
 fn input_and_compile_error(mut item: TokenStream, err: syn::Error) -> TokenStream {
     let compile_err = TokenStream::from(err.to_compile_error());
     item.extend(compile_err);
     item
-}
\ No newline at end of file
+}
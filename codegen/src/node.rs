@@ -0,0 +1,313 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{punctuated::Punctuated, Token};
+
+// 端点方向
+#[derive(PartialEq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+// 一个输入或输出端点, 对应一个 Option<RecordBatch> 字段
+struct Port {
+    field: syn::Ident,
+    direction: Direction,
+    // ArrowType 的变体名, 例如 Utf8
+    arrow_type: syn::Ident,
+}
+
+// 参数字段及其 UI 提示
+struct Params {
+    field: syn::Ident,
+    ty: syn::Type,
+    // 参数名到 ui:widget 的映射
+    widgets: Vec<(String, syn::LitStr)>,
+}
+
+pub struct WorkflowNode {
+    name: syn::Ident,
+    generics: syn::Generics,
+    ports: Vec<Port>,
+    params: Option<Params>,
+}
+
+impl WorkflowNode {
+    pub fn new(input: syn::DeriveInput) -> syn::Result<Self> {
+        let syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) = &input.data
+        else {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "WorkflowNode can only be derived for structs with named fields",
+            ));
+        };
+
+        let mut ports = vec![];
+        let mut params: Option<Params> = None;
+
+        for field in &fields.named {
+            let ident = field.ident.clone().unwrap();
+            for attr in &field.attrs {
+                let direction = if attr.path().is_ident("input") {
+                    Direction::Input
+                } else if attr.path().is_ident("output") {
+                    Direction::Output
+                } else if attr.path().is_ident("params") {
+                    if params.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            attr,
+                            "Only one field can be marked as #[params]",
+                        ));
+                    }
+                    params = Some(Params {
+                        field: ident.clone(),
+                        ty: field.ty.clone(),
+                        widgets: Self::widgets(field)?,
+                    });
+                    continue;
+                } else {
+                    continue;
+                };
+
+                // #[input(Utf8)] / #[output(Float64)]
+                let arrow_type = attr.parse_args::<syn::Ident>().map_err(|mut err| {
+                    err.combine(syn::Error::new(
+                        err.span(),
+                        "expected an ArrowType variant, e.g. #[input(Utf8)]",
+                    ));
+                    err
+                })?;
+                ports.push(Port {
+                    field: ident.clone(),
+                    direction,
+                    arrow_type,
+                });
+            }
+
+            if field.attrs.iter().any(|attr| attr.path().is_ident("ui"))
+                && params.as_ref().map(|params| &params.field) != Some(&ident)
+            {
+                return Err(syn::Error::new_spanned(
+                    &field.ident,
+                    "#[ui(...)] can only be used on the #[params] field",
+                ));
+            }
+        }
+
+        Ok(Self {
+            name: input.ident,
+            generics: input.generics,
+            ports,
+            params,
+        })
+    }
+
+    // 解析 #[ui(field = "widget", ...)]
+    fn widgets(field: &syn::Field) -> syn::Result<Vec<(String, syn::LitStr)>> {
+        let mut widgets = vec![];
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("ui")) {
+            let options = attr
+                .parse_args_with(Punctuated::<syn::MetaNameValue, Token![,]>::parse_terminated)?;
+            for nv in options {
+                let Some(key) = nv.path.get_ident() else {
+                    return Err(syn::Error::new_spanned(
+                        nv.path,
+                        "expected a params field name",
+                    ));
+                };
+                let syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(widget),
+                    ..
+                }) = nv.value
+                else {
+                    return Err(syn::Error::new_spanned(
+                        nv.value,
+                        "Attribute ui expects literal string widgets",
+                    ));
+                };
+                widgets.push((key.to_string(), widget));
+            }
+        }
+        Ok(widgets)
+    }
+
+    // get_input / get_output 的分支
+    fn get_arms(&self, direction: Direction) -> Vec<TokenStream2> {
+        self.ports
+            .iter()
+            .filter(|port| port.direction == direction)
+            .map(|port| {
+                let field = &port.field;
+                let key = field.to_string();
+                quote! { #key => self.#field.as_ref(), }
+            })
+            .collect()
+    }
+
+    // set_input / set_output 的分支
+    fn set_arms(&self, direction: Direction) -> Vec<TokenStream2> {
+        self.ports
+            .iter()
+            .filter(|port| port.direction == direction)
+            .map(|port| {
+                let field = &port.field;
+                let key = field.to_string();
+                quote! { #key => self.#field = ::core::option::Option::Some(data), }
+            })
+            .collect()
+    }
+}
+
+impl ToTokens for WorkflowNode {
+    fn to_tokens(&self, output: &mut TokenStream2) {
+        let name = &self.name;
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        let get_inputs = self.get_arms(Direction::Input);
+        let get_outputs = self.get_arms(Direction::Output);
+        let set_inputs = self.set_arms(Direction::Input);
+        let set_outputs = self.set_arms(Direction::Output);
+
+        let endpoints = self.ports.iter().map(|port| {
+            let key = port.field.to_string();
+            let arrow_type = &port.arrow_type;
+            quote! {
+                endpoints.insert(
+                    ::std::string::String::from(#key),
+                    ::autoflow::node_trait::ArrowType::#arrow_type,
+                );
+            }
+        });
+
+        let (data_schema, get_data, ui_schema) = match &self.params {
+            Some(Params { field, ty, widgets }) => {
+                let widgets = widgets.iter().map(|(key, widget)| {
+                    quote! {
+                        ui.insert(
+                            ::std::string::String::from(#key),
+                            ::autoflow::__private::serde_json::json!({ "ui:widget": #widget }),
+                        );
+                    }
+                });
+                (
+                    quote! {
+                        let schema = ::autoflow::__private::schemars::schema_for!(#ty);
+                        ::autoflow::__private::serde_json::to_value(schema)
+                            .unwrap_or(::autoflow::__private::serde_json::Value::Null)
+                    },
+                    quote! {
+                        ::autoflow::__private::serde_json::to_value(&self.#field)
+                            .ok()?
+                            .get(key)
+                            .cloned()
+                    },
+                    quote! {
+                        let mut ui = ::autoflow::__private::serde_json::Map::new();
+                        #(#widgets)*
+                        ::autoflow::__private::serde_json::Value::Object(ui)
+                    },
+                )
+            }
+            // 没有参数的节点
+            None => (
+                quote! {
+                    ::autoflow::__private::serde_json::json!({ "type": "object", "properties": {} })
+                },
+                quote! {
+                    let _ = key;
+                    ::core::option::Option::None
+                },
+                quote! {
+                    ::autoflow::__private::serde_json::json!({})
+                },
+            ),
+        };
+
+        let stream = quote! {
+            impl #impl_generics ::autoflow::node_trait::NodeDefinition for #name #ty_generics #where_clause {
+                fn get_input(
+                    &self,
+                    key: &str,
+                ) -> ::core::option::Option<&::autoflow::__private::arrow::record_batch::RecordBatch> {
+                    match key {
+                        #(#get_inputs)*
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                fn get_output(
+                    &self,
+                    key: &str,
+                ) -> ::core::option::Option<&::autoflow::__private::arrow::record_batch::RecordBatch> {
+                    match key {
+                        #(#get_outputs)*
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                fn endpoints(
+                    &self,
+                ) -> ::std::collections::HashMap<::std::string::String, ::autoflow::node_trait::ArrowType> {
+                    let mut endpoints = ::std::collections::HashMap::new();
+                    #(#endpoints)*
+                    endpoints
+                }
+
+                fn data_schema(&self) -> ::autoflow::__private::serde_json::Value {
+                    #data_schema
+                }
+
+                fn ui_schema(&self) -> ::autoflow::__private::serde_json::Value {
+                    #ui_schema
+                }
+
+                fn get_data(
+                    &self,
+                    key: &str,
+                ) -> ::core::option::Option<::autoflow::__private::serde_json::Value> {
+                    #get_data
+                }
+
+                fn set_input(
+                    &mut self,
+                    key: &str,
+                    data: ::autoflow::__private::arrow::record_batch::RecordBatch,
+                ) {
+                    match key {
+                        #(#set_inputs)*
+                        _ => {}
+                    }
+                }
+
+                fn set_output(
+                    &mut self,
+                    key: &str,
+                    data: ::autoflow::__private::arrow::record_batch::RecordBatch,
+                ) {
+                    match key {
+                        #(#set_outputs)*
+                        _ => {}
+                    }
+                }
+            }
+        };
+
+        output.extend(stream);
+    }
+}
+
+pub(crate) fn derive(input: TokenStream) -> TokenStream {
+    let input = match syn::parse::<syn::DeriveInput>(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    match WorkflowNode::new(input) {
+        Ok(node) => node.into_token_stream().into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
@@ -1,11 +1,11 @@
 use crate::input_and_compile_error;
 use proc_macro::TokenStream;
-use proc_macro2::{Span, TokenStream as TokenStream2};
+use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens};
-use syn::{punctuated::Punctuated, LitStr, Token};
+use syn::{ext::IdentExt, punctuated::Punctuated, LitStr, Token};
 
 pub struct TaskArgs {
-    // 路由路径，例如 "/foo"
+    // 任务类型，例如 "send_email"
     pub(crate) path: syn::LitStr,
     // 注解的额外参数, 当前只允许name参数
     pub(crate) options: Punctuated<syn::MetaNameValue, Token![,]>,
@@ -16,12 +16,15 @@ impl syn::parse::Parse for TaskArgs {
         let path = input.parse::<syn::LitStr>().map_err(|mut err| {
             err.combine(syn::Error::new(
                 err.span(),
-                r#"invalid service definition, expected #[<method>("<path>")]"#,
+                r#"invalid task definition, expected #[task("<task_type>")]"#,
             ));
 
             err
         })?;
 
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
         let options = input.parse_terminated(syn::MetaNameValue::parse, Token![,])?;
 
         Ok(Self { path, options })
@@ -51,6 +54,11 @@ impl Args {
                         "Attribute name expects literal string",
                     ));
                 }
+            } else {
+                return Err(syn::Error::new_spanned(
+                    nv.path,
+                    "Unknown attribute key is specified; allowed: name",
+                ));
             }
         }
         Ok(Args {
@@ -60,11 +68,24 @@ impl Args {
     }
 }
 
+// 处理函数的参数
+enum Param {
+    // 按参数名从任务数据中取出的字段
+    Field {
+        ident: syn::Ident,
+        ty: Box<syn::Type>,
+    },
+    // 引用类型的参数接收任务本身
+    Task,
+}
+
 pub struct Task {
     // 被注解的函数名
     name: syn::Ident,
 
-    args: Vec<Args>,
+    args: Args,
+
+    params: Vec<Param>,
 
     // handler函数ast
     ast: syn::ItemFn,
@@ -85,17 +106,70 @@ impl Task {
             ));
         }
 
+        let params = ast
+            .sig
+            .inputs
+            .iter()
+            .map(Self::param)
+            .collect::<syn::Result<Vec<_>>>()?;
+
         Ok(Self {
             name,
-            args: vec![args],
+            args,
+            params,
             ast,
         })
     }
+
+    fn param(input: &syn::FnArg) -> syn::Result<Param> {
+        let syn::FnArg::Typed(pat_type) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Task handler cannot take a self receiver",
+            ));
+        };
+
+        // 引用参数只能是 &Task, 其他引用类型无法从任务数据中提取
+        if let syn::Type::Reference(reference) = &*pat_type.ty {
+            return match &*reference.elem {
+                syn::Type::Path(path)
+                    if reference.mutability.is_none()
+                        && path
+                            .path
+                            .segments
+                            .last()
+                            .is_some_and(|segment| segment.ident == "Task") =>
+                {
+                    Ok(Param::Task)
+                }
+                _ => Err(syn::Error::new_spanned(
+                    &pat_type.ty,
+                    "Task handler reference parameters other than &Task are not supported",
+                )),
+            };
+        }
+
+        match &*pat_type.pat {
+            syn::Pat::Ident(pat) => Ok(Param::Field {
+                ident: pat.ident.clone(),
+                ty: pat_type.ty.clone(),
+            }),
+            pat => Err(syn::Error::new_spanned(
+                pat,
+                "Task handler arguments must be plain identifiers matching task data fields",
+            )),
+        }
+    }
 }
 
 impl ToTokens for Task {
     fn to_tokens(&self, output: &mut TokenStream2) {
-        let Self { name, ast, args } = self;
+        let Self {
+            name,
+            args,
+            params,
+            ast,
+        } = self;
 
         #[allow(unused_variables)] // used when force-pub feature is disabled
         let vis = &ast.vis;
@@ -104,33 +178,64 @@ impl ToTokens for Task {
         #[cfg(feature = "compat-routing-macros-force-pub")]
         let vis = syn::Visibility::Public(<Token![pub]>::default());
 
-        let registrations: TokenStream2 = args
-            .iter()
-            .map(|args| {
-                let Args {
-                    path,
-                    resource_name,
-                } = args;
+        let Args {
+            path,
+            resource_name,
+        } = args;
+
+        let resource_name = resource_name
+            .as_ref()
+            .map_or_else(|| name.to_string(), LitStr::value);
 
-                let resource_name = resource_name
-                    .as_ref()
-                    .map_or_else(|| name.to_string(), LitStr::value);
+        // 处理函数嵌入到 handle 中, 与生成的结构体同名也不会冲突
+        let mut inner = ast.clone();
+        inner.vis = syn::Visibility::Inherited;
 
+        let extractions = params.iter().map(|param| match param {
+            Param::Field { ident, ty } => {
+                let field = ident.unraw().to_string();
                 quote! {
-                    todo!
+                    let #ident: #ty = ::autoflow::__private::extract_arg(__task, #field)?;
                 }
-            })
-            .collect();
+            }
+            Param::Task => quote! {},
+        });
+
+        let call_args = params.iter().map(|param| match param {
+            Param::Field { ident, .. } => quote! { #ident },
+            Param::Task => quote! { __task },
+        });
+
+        let await_call = ast.sig.asyncness.map(|_| quote! { .await });
 
         let stream = quote! {
             #[allow(non_camel_case_types, missing_docs)]
             #vis struct #name;
 
-            impl ::actix_web::dev::HttpServiceFactory for #name {
-                fn register(self, __config: &mut actix_web::dev::AppService) {
-                    #ast
-                    #registrations
+            #[::autoflow::__private::async_trait]
+            impl ::autoflow::handler2::TaskHandler for #name {
+                fn for_task(&self) -> &'static str {
+                    #path
+                }
+
+                fn name(&self) -> &'static str {
+                    #resource_name
                 }
+
+                async fn handle(
+                    &self,
+                    __task: &::autoflow::task::Task,
+                ) -> ::core::result::Result<(), ::autoflow::handler2::TaskError> {
+                    #inner
+                    #(#extractions)*
+                    ::autoflow::__private::IntoTaskResult::into_task_result(
+                        #name(#(#call_args),*)#await_call
+                    )
+                }
+            }
+
+            ::autoflow::__private::inventory::submit! {
+                ::autoflow::registry::HandlerRegistration::new(|| ::std::sync::Arc::new(#name))
             }
         };
 
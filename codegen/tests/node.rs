@@ -0,0 +1,126 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, StringArray},
+    record_batch::RecordBatch,
+};
+use autoflow::node_trait::{ArrowType, NodeDefinition, NodeTrait};
+use codegen::WorkflowNode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+struct FilterParams {
+    /// 匹配的前缀
+    prefix: String,
+    threshold: Option<f64>,
+}
+
+#[derive(Default, WorkflowNode)]
+struct FilterNode {
+    #[input(Utf8)]
+    source: Option<RecordBatch>,
+
+    #[output(Utf8)]
+    matched: Option<RecordBatch>,
+
+    #[output(Int32)]
+    count: Option<RecordBatch>,
+
+    #[params]
+    #[ui(prefix = "text", threshold = "range")]
+    params: FilterParams,
+}
+
+impl NodeTrait for FilterNode {
+    fn execute(&mut self) -> Result<HashMap<String, RecordBatch>, Box<dyn Error>> {
+        let source = self.get_input("source").ok_or("缺少输入 source")?.clone();
+        self.set_output("matched", source.clone());
+        Ok(HashMap::from([("matched".to_string(), source)]))
+    }
+}
+
+// 没有参数的节点
+#[derive(Default, WorkflowNode)]
+struct PassNode {
+    #[input(Float64)]
+    input: Option<RecordBatch>,
+}
+
+fn batch(values: Vec<&str>) -> RecordBatch {
+    let array: ArrayRef = Arc::new(StringArray::from(values));
+    RecordBatch::try_from_iter(vec![("value", array)]).unwrap()
+}
+
+#[test]
+fn test_endpoints() {
+    let node = FilterNode::default();
+    assert_eq!(
+        node.endpoints(),
+        HashMap::from([
+            ("source".to_string(), ArrowType::Utf8),
+            ("matched".to_string(), ArrowType::Utf8),
+            ("count".to_string(), ArrowType::Int32),
+        ])
+    );
+}
+
+#[test]
+fn test_port_storage() {
+    let mut node = FilterNode::default();
+    assert!(node.get_input("source").is_none());
+
+    node.set_input("source", batch(vec!["a", "b"]));
+    // 输出端点不能按输入端点名读取
+    assert!(node.get_output("source").is_none());
+    // 未声明的端点被忽略
+    node.set_input("unknown", batch(vec!["c"]));
+    assert!(node.get_input("unknown").is_none());
+
+    let outputs = node.execute().unwrap();
+    assert_eq!(outputs["matched"].num_rows(), 2);
+    assert_eq!(node.get_output("matched").unwrap().num_rows(), 2);
+}
+
+#[test]
+fn test_schemas_and_data() {
+    let node = FilterNode {
+        params: FilterParams {
+            prefix: "user_".to_string(),
+            threshold: Some(0.5),
+        },
+        ..Default::default()
+    };
+
+    let schema = node.data_schema();
+    assert_eq!(schema["title"], "FilterParams");
+    assert_eq!(schema["required"], json!(["prefix"]));
+    assert_eq!(schema["properties"]["prefix"]["type"], "string");
+
+    assert_eq!(
+        node.ui_schema(),
+        json!({
+            "prefix": {"ui:widget": "text"},
+            "threshold": {"ui:widget": "range"},
+        })
+    );
+
+    assert_eq!(node.get_data("prefix"), Some(json!("user_")));
+    assert_eq!(node.get_data("threshold"), Some(json!(0.5)));
+    assert_eq!(node.get_data("missing"), None);
+}
+
+#[test]
+fn test_node_without_params() {
+    let mut node = PassNode::default();
+    assert_eq!(
+        node.endpoints(),
+        HashMap::from([("input".to_string(), ArrowType::Float64)])
+    );
+    assert_eq!(node.ui_schema(), json!({}));
+    assert_eq!(node.get_data("anything"), None);
+
+    node.set_input("input", batch(vec!["x"]));
+    assert!(node.get_input("input").is_some());
+}
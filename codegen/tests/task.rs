@@ -1,6 +1,92 @@
+use std::sync::Mutex;
 
+use autoflow::{
+    handler2::{TaskError, TaskHandler, TaskHandlerExec},
+    registry::HandlerRegistry,
+    task::Task,
+};
 use codegen::task;
+use serde_json::json;
 
-#[task("/config")]
-fn config() {
-}
\ No newline at end of file
+static SENT: Mutex<Vec<(String, Option<u32>)>> = Mutex::new(vec![]);
+
+#[task("send_email", name = "发送邮件")]
+async fn send_email(to: String, retries: Option<u32>) -> Result<(), TaskError> {
+    if to.is_empty() {
+        return Err(TaskError::Fatal("收件人为空".to_string()));
+    }
+    SENT.lock().unwrap().push((to, retries));
+    Ok(())
+}
+
+#[task("echo")]
+async fn echo(task: &Task, r#type: String) -> Result<(), TaskError> {
+    if task.task_type == "echo" && r#type == "ping" {
+        Ok(())
+    } else {
+        Err(TaskError::Retryable(format!("unexpected {}", r#type)))
+    }
+}
+
+// 同步函数同样可以作为处理器
+#[task("config")]
+fn config() -> Result<(), TaskError> {
+    Ok(())
+}
+
+#[test]
+fn test_task_type_and_name() {
+    assert_eq!(send_email.for_task(), "send_email");
+    assert_eq!(send_email.name(), "发送邮件");
+    // 未指定 name 时使用函数名
+    assert_eq!(config.for_task(), "config");
+    assert_eq!(config.name(), "config");
+}
+
+#[tokio::test]
+async fn test_arguments_extracted_from_payload() {
+    let task = Task::new(
+        "send_email".to_string(),
+        json!({"to": "a@example.com", "retries": 2}),
+    );
+    assert_eq!(send_email.exec(&task).await, Ok(()));
+
+    // 缺少的 Option 参数为 None
+    let task = Task::new("send_email".to_string(), json!({"to": "b@example.com"}));
+    assert_eq!(send_email.exec(&task).await, Ok(()));
+
+    let sent = SENT.lock().unwrap().clone();
+    assert!(sent.contains(&("a@example.com".to_string(), Some(2))));
+    assert!(sent.contains(&("b@example.com".to_string(), None)));
+
+    // 函数返回的错误原样传出
+    let task = Task::new("send_email".to_string(), json!({"to": ""}));
+    assert_eq!(
+        send_email.exec(&task).await,
+        Err(TaskError::Fatal("收件人为空".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_payload_is_fatal() {
+    let task = Task::new("send_email".to_string(), json!({"retries": 1}));
+    let result = send_email.exec(&task).await;
+    assert!(matches!(result, Err(TaskError::Fatal(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn test_task_reference_and_raw_identifiers() {
+    let task = Task::new("echo".to_string(), json!({"type": "ping"}));
+    assert_eq!(echo.exec(&task).await, Ok(()));
+
+    let task = Task::new("config".to_string(), json!({}));
+    assert_eq!(config.exec(&task).await, Ok(()));
+}
+
+#[test]
+fn test_generated_handlers_register() {
+    let mut registry = HandlerRegistry::new();
+    registry.register(std::sync::Arc::new(send_email)).unwrap();
+    registry.register(std::sync::Arc::new(echo)).unwrap();
+    assert_eq!(registry.task_types(), vec!["echo", "send_email"]);
+}
@@ -0,0 +1,10 @@
+use autoflow::handler2::TaskError;
+use codegen::task;
+
+#[task("greet")]
+async fn greet(name: &str) -> Result<(), TaskError> {
+    println!("{}", name);
+    Ok(())
+}
+
+fn main() {}
@@ -0,0 +1,96 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+/// A named set of excluded dates and blackout windows.
+///
+/// Attach a calendar to a [`Trigger`](crate::trigger::Trigger) to keep it from
+/// firing on public holidays or during maintenance windows. Dates are
+/// evaluated in the calendar's own time zone; blackout windows are absolute.
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    pub name: String,
+
+    /// Time zone the excluded dates are evaluated in.
+    pub timezone: Tz,
+
+    /// Whole days on which nothing fires.
+    pub excluded_dates: BTreeSet<NaiveDate>,
+
+    /// Time ranges `[start, end)` in which nothing fires.
+    pub blackouts: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Calendar {
+    /// Creates an empty calendar.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            timezone: Tz::UTC,
+            excluded_dates: BTreeSet::new(),
+            blackouts: Vec::new(),
+        }
+    }
+
+    /// Sets the time zone the excluded dates are evaluated in.
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Excludes a whole day.
+    pub fn exclude_date(mut self, date: NaiveDate) -> Self {
+        self.excluded_dates.insert(date);
+        self
+    }
+
+    /// Excludes the time range `[start, end)`.
+    pub fn exclude_range(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.blackouts.push((start, end));
+        self
+    }
+
+    /// Returns whether nothing may fire at `instant`.
+    pub fn is_excluded(&self, instant: DateTime<Utc>) -> bool {
+        let date = instant.with_timezone(&self.timezone).date_naive();
+        self.excluded_dates.contains(&date)
+            || self
+                .blackouts
+                .iter()
+                .any(|(start, end)| *start <= instant && instant < *end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_excluded_dates_use_calendar_time_zone() {
+        // 上海的元旦从 UTC 前一天 16:00 开始
+        let calendar = Calendar::new("holidays")
+            .with_timezone(chrono_tz::Asia::Shanghai)
+            .exclude_date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+
+        assert!(calendar.is_excluded(utc("2023-12-31T16:00:00Z")));
+        assert!(calendar.is_excluded(utc("2024-01-01T15:59:59Z")));
+        assert!(!calendar.is_excluded(utc("2023-12-31T15:59:59Z")));
+        assert!(!calendar.is_excluded(utc("2024-01-01T16:00:00Z")));
+    }
+
+    #[test]
+    fn test_blackout_windows() {
+        let calendar = Calendar::new("maintenance")
+            .exclude_range(utc("2024-01-01T02:00:00Z"), utc("2024-01-01T04:00:00Z"));
+
+        assert!(!calendar.is_excluded(utc("2024-01-01T01:59:59Z")));
+        assert!(calendar.is_excluded(utc("2024-01-01T02:00:00Z")));
+        assert!(calendar.is_excluded(utc("2024-01-01T03:59:59Z")));
+        assert!(!calendar.is_excluded(utc("2024-01-01T04:00:00Z")));
+    }
+}
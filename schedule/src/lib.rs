@@ -1,4 +1,7 @@
 pub mod scheduler;
 pub mod planner;
 pub mod trigger;
-pub mod task;
\ No newline at end of file
+pub mod task;
+pub mod store;
+pub mod calendar;
+pub mod clock;
\ No newline at end of file
@@ -1,17 +1,35 @@
+use std::sync::Arc;
+
 use chrono::{DateTime, Utc};
 
-use crate::{task::Task, trigger::Trigger};
+use crate::{
+    clock::{Clock, SystemClock},
+    task::Task,
+    trigger::Trigger,
+};
 
 /// Planner struct responsible for generating task schedules based on triggers.
 pub struct Planner {
     /// List of triggers, each representing a scheduled workflow.
     triggers: Vec<Trigger>,
+
+    /// Source of the current time.
+    clock: Arc<dyn Clock>,
 }
 
 impl Planner {
     /// Creates a new `Planner` instance with a list of triggers.
     pub fn new(triggers: Vec<Trigger>) -> Self {
-        Self { triggers }
+        Self {
+            triggers,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Replaces the system clock, e.g. with a `ManualClock` in tests.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 
     /// Generates a batch of tasks based on triggers and the specified time window.
@@ -25,36 +43,66 @@ impl Planner {
     /// * `Vec<Task>` - A vector of `Task` objects scheduled to run within the specified time window.
     pub fn generate_tasks(&self, end: DateTime<Utc>) -> Vec<Task> {
         let mut tasks = Vec::new();
-        let now = Utc::now();
+        let now = self.clock.now();
 
         for trigger in &self.triggers {
-            // 使用 Trigger 的 next_run_times 方法获取时间窗口内的所有执行时间
-            for run_at in trigger.next_run_times(now, end) {
-                // Create a new Task
-                let task = Task {
-                    id: format!("{}-{}", trigger.id, run_at.timestamp()), // Unique task ID
-                    run_at,
-                    workflow_id: trigger.workflow_id.clone(),
-                    trigger_id: trigger.id.clone(),
-                };
-                tasks.push(task);
-            }
+            tasks.extend(Self::plan_trigger(trigger, now, end, 0));
         }
 
         tasks
     }
+
+    /// Generates the tasks of a single trigger within `[start, end)`.
+    ///
+    /// Every kind of trigger is planned the same way; the trigger's `max_fires`
+    /// limit is applied on top of the firings already counted in `fired`. The
+    /// window selects fire times before jitter; each task runs at its fire time
+    /// plus the trigger's jitter.
+    ///
+    /// # Arguments
+    ///
+    /// * `trigger` - The trigger to plan.
+    /// * `start` - The start of the time window, inclusive.
+    /// * `end` - The end of the time window, exclusive.
+    /// * `fired` - How many firings of the trigger were already executed or queued.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Task>` - The tasks of the trigger, ordered by run time.
+    pub fn plan_trigger(trigger: &Trigger, start: DateTime<Utc>, end: DateTime<Utc>, fired: u32) -> Vec<Task> {
+        let remaining = trigger.remaining_fires(fired).unwrap_or(usize::MAX);
+        let jitter = trigger.jitter();
+
+        // 使用 Trigger 的 next_run_times 方法获取时间窗口内的所有执行时间
+        trigger
+            .next_run_times(start, end)
+            .into_iter()
+            .take(remaining)
+            .map(|run_at| Task {
+                id: Task::canonical_id(&trigger.id, run_at), // Unique task ID
+                run_at: run_at + jitter,
+                workflow_id: trigger.workflow_id.clone(),
+                trigger_id: trigger.id.clone(),
+            })
+            .collect()
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
     use chrono::{Duration, Utc};
 
     fn create_trigger(id: &str, cron_expr: &str, workflow_id: &str) -> Trigger {
         Trigger::new(id, cron_expr, workflow_id)
     }
 
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
     #[test]
     fn test_generate_tasks_with_valid_triggers() {
         // 设置触发器列表和时间窗口
@@ -62,26 +110,31 @@ mod tests {
             create_trigger("trigger1", "*/5 * * * * *", "workflow1"),
             create_trigger("trigger2", "*/10 * * * * *", "workflow2"),
         ];
-        let planner = Planner::new(triggers);
-        let future_time = Utc::now() + Duration::seconds(20); // 未来20秒的时间窗口
+        let now = utc("2024-01-01T00:00:00Z");
+        let planner = Planner::new(triggers).with_clock(ManualClock::new(now));
+        let future_time = now + Duration::seconds(20); // 未来20秒的时间窗口
 
         // 生成任务
         let tasks = planner.generate_tasks(future_time);
 
-        // 检查任务是否生成，并验证每个任务的时间边界
-        assert!(!tasks.is_empty(), "Expected tasks to be generated.");
-        let now = Utc::now();
-        for task in &tasks {
-            assert!(task.run_at >= now && task.run_at < future_time,
-                    "Task run_at time is not within the specified time window.");
-        }
+        // 窗口 [00:00:00, 00:00:20) 内每 5 秒和每 10 秒的触发
+        let run_times = |trigger_id: &str| {
+            tasks.iter().filter(|task| task.trigger_id == trigger_id).map(|task| task.run_at).collect::<Vec<_>>()
+        };
+        assert_eq!(tasks.len(), 6);
+        assert_eq!(
+            run_times("trigger1"),
+            vec![now, now + Duration::seconds(5), now + Duration::seconds(10), now + Duration::seconds(15)]
+        );
+        assert_eq!(run_times("trigger2"), vec![now, now + Duration::seconds(10)]);
     }
 
     #[test]
     fn test_generate_tasks_with_empty_triggers() {
         // 创建一个空的触发器列表
-        let planner = Planner::new(Vec::new());
-        let future_time = Utc::now() + Duration::seconds(20); // 未来20秒的时间窗口
+        let now = utc("2024-01-01T00:00:00Z");
+        let planner = Planner::new(Vec::new()).with_clock(ManualClock::new(now));
+        let future_time = now + Duration::seconds(20); // 未来20秒的时间窗口
 
         // 生成任务
         let tasks = planner.generate_tasks(future_time);
@@ -98,24 +151,57 @@ mod tests {
             create_trigger("trigger2", "*/10 * * * * *", "workflow2"),
             create_trigger("trigger3", "*/15 * * * * *", "workflow3"),
         ];
-        let planner = Planner::new(triggers);
-        let future_time = Utc::now() + Duration::seconds(20); // 未来20秒的时间窗口
+        let now = utc("2024-01-01T00:00:00Z");
+        let planner = Planner::new(triggers).with_clock(ManualClock::new(now));
+        let future_time = now + Duration::seconds(20); // 未来20秒的时间窗口
 
         // 生成任务
         let tasks = planner.generate_tasks(future_time);
 
-        // 验证是否生成任务，且所有任务的时间均在时间窗口内
-        assert!(!tasks.is_empty(), "Expected tasks to be generated for multiple triggers.");
-        let now = Utc::now();
-        for task in &tasks {
-            assert!(task.run_at >= now && task.run_at < future_time,
-                    "Task run_at time is not within the specified time window.");
-            assert!(task.trigger_id == "trigger1" || task.trigger_id == "trigger2" || task.trigger_id == "trigger3",
-                    "Unexpected trigger_id in generated task.");
-        }
+        // 所有任务的时间均在时间窗口内
+        assert!(tasks.iter().all(|task| task.run_at >= now && task.run_at < future_time),
+                "Task run_at time is not within the specified time window.");
 
-        // 验证每个触发器的任务数量是否合理
-        let task_counts = tasks.iter().filter(|task| task.trigger_id == "trigger1").count();
-        assert!(task_counts > 0, "Expected tasks from trigger1.");
+        // 验证每个触发器的任务数量
+        let count = |trigger_id: &str| tasks.iter().filter(|task| task.trigger_id == trigger_id).count();
+        assert_eq!(count("trigger1"), 4);
+        assert_eq!(count("trigger2"), 2);
+        assert_eq!(count("trigger3"), 2);
+        assert_eq!(tasks.len(), 8);
+    }
+
+    #[test]
+    fn test_generate_tasks_with_mixed_trigger_kinds() {
+        // 不同类型的触发器统一生成任务，max_fires 限制触发次数
+        let now = utc("2024-01-01T00:00:00Z");
+        let triggers = vec![
+            Trigger::interval("interval", Duration::seconds(2), now, "workflow1").with_max_fires(3),
+            Trigger::once("once", now + Duration::seconds(5), "workflow2"),
+            create_trigger("cron", "* * * * * *", "workflow3").with_max_fires(1),
+        ];
+        let planner = Planner::new(triggers).with_clock(ManualClock::new(now));
+
+        let tasks = planner.generate_tasks(now + Duration::seconds(20));
+        let count = |trigger_id: &str| tasks.iter().filter(|task| task.trigger_id == trigger_id).count();
+        assert_eq!(count("interval"), 3);
+        assert_eq!(count("once"), 1);
+        assert_eq!(count("cron"), 1);
+    }
+
+    #[test]
+    fn test_generate_tasks_with_manual_clock() {
+        let start = utc("2024-01-01T00:00:00Z");
+        let clock = ManualClock::new(start);
+        let planner = Planner::new(vec![create_trigger("trigger1", "*/10 * * * * *", "workflow1")])
+            .with_clock(clock.clone());
+
+        let tasks = planner.generate_tasks(start + Duration::seconds(30));
+        assert_eq!(tasks.len(), 3);
+
+        // 推进时钟后，窗口起点随之移动
+        clock.advance(Duration::seconds(20));
+        let tasks = planner.generate_tasks(start + Duration::seconds(30));
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].run_at, start + Duration::seconds(20));
     }
 }
@@ -1,23 +1,111 @@
 use actix::prelude::*;
 use actix::Actor;
-use chrono::Utc;
-use std::collections::{BinaryHeap, HashSet};
+use chrono::{DateTime, Utc};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::clock::{Clock, SystemClock};
+use crate::planner::Planner;
+use crate::store::{SchedulerState, StateStore};
 use crate::task::Task;
+use crate::trigger::{MisfirePolicy, OverlapPolicy, Trigger};
+
+/// Default length of the rolling look-ahead window planned on every tick.
+pub const DEFAULT_LOOKAHEAD: Duration = Duration::from_secs(60);
+
+/// Default real-time interval between two ticks of the actor.
+pub const DEFAULT_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time ids of past tasks are remembered for deduplication.
+pub const DEFAULT_DEDUP_RETENTION: Duration = Duration::from_secs(60 * 60);
 
 /// Executor trait for handling task execution
+///
+/// Triggers with an overlap policy other than `Allow` need to know when a run
+/// finishes: the executor reports it by sending [`RunCompleted`] to the
+/// scheduler, e.g. through an address captured with `Scheduler::create`.
 pub trait Executor: Send + Sync + 'static {
     fn execute(&self, task: &Task);
+
+    /// Cancels a running task, for triggers with `OverlapPolicy::CancelPrevious`.
+    ///
+    /// The default does nothing; the scheduler no longer treats the task as running.
+    fn cancel(&self, _task: &Task) {}
+}
+
+/// A trigger owned by the scheduler, together with its planning progress.
+struct TriggerState {
+    trigger: Trigger,
+
+    /// Firings before this time have already been planned.
+    watermark: DateTime<Utc>,
+
+    /// Paused triggers are neither planned nor executed.
+    paused: bool,
 }
 
-/// Scheduler struct responsible for executing tasks.
+/// Scheduler struct responsible for planning and executing tasks.
+///
+/// The scheduler owns a set of triggers. On every tick it plans each active
+/// trigger from its watermark up to `now + lookahead` and advances the
+/// watermark, so every upcoming firing is queued exactly once.
+///
+/// With a [`StateStore`] the last fire time of each trigger and the pending
+/// tasks survive a restart. Firings missed while the scheduler was down are
+/// handled by the trigger's [`MisfirePolicy`] when the trigger is added again.
 pub struct Scheduler<E: Executor> {
     /// BinaryHeap of scheduled tasks, ordered by run time.
     pub tasks: BinaryHeap<Task>,
 
-    /// A set of unique task identifiers to prevent duplicate tasks in the same time window.
-    unique_task_ids: HashSet<String>,
+    /// Unique task identifiers with their run times, to prevent duplicate tasks
+    /// in the same time window. Pruned once the run time is older than
+    /// `dedup_retention`.
+    unique_task_ids: HashMap<String, DateTime<Utc>>,
+
+    /// How long ids of past tasks are remembered.
+    dedup_retention: Duration,
+
+    /// Triggers owned by the scheduler, keyed by trigger id.
+    triggers: HashMap<String, TriggerState>,
+
+    /// Length of the rolling look-ahead window.
+    lookahead: Duration,
+
+    /// Maximum number of tasks dispatched per tick, unlimited if `None`.
+    dispatch_limit: Option<usize>,
+
+    /// Number of tasks dispatched since the current tick started.
+    dispatched: usize,
+
+    /// Time of the last firing of each trigger.
+    last_fired: HashMap<String, DateTime<Utc>>,
+
+    /// Number of firings of each trigger, used for `max_fires`.
+    fire_counts: HashMap<String, u32>,
+
+    /// Runs still in progress, keyed by trigger id. Only tracked for triggers
+    /// whose overlap policy is not `Allow`.
+    running: HashMap<String, Vec<Task>>,
+
+    /// Firings waiting for the previous run to complete, keyed by trigger id.
+    queued: HashMap<String, VecDeque<Task>>,
+
+    /// Last fire times loaded from the store whose triggers were not added yet.
+    recovered: HashMap<String, DateTime<Utc>>,
+
+    /// Where the state is persisted, if anywhere.
+    store: Option<Box<dyn StateStore>>,
+
+    /// Whether the state changed since it was last persisted.
+    dirty: bool,
+
+    /// Source of the current time.
+    clock: Arc<dyn Clock>,
+
+    /// Real-time interval between two ticks of the actor.
+    tick_interval: Duration,
 
     /// Executor trait for handling task execution
     executor: E,
@@ -27,60 +115,473 @@ impl<E: Executor> Scheduler<E> {
     pub fn new(executor: E) -> Self {
         Self {
             tasks: BinaryHeap::new(),
-            unique_task_ids: HashSet::new(),
+            unique_task_ids: HashMap::new(),
+            dedup_retention: DEFAULT_DEDUP_RETENTION,
+            triggers: HashMap::new(),
+            lookahead: DEFAULT_LOOKAHEAD,
+            dispatch_limit: None,
+            dispatched: 0,
+            last_fired: HashMap::new(),
+            fire_counts: HashMap::new(),
+            running: HashMap::new(),
+            queued: HashMap::new(),
+            recovered: HashMap::new(),
+            store: None,
+            dirty: false,
+            clock: Arc::new(SystemClock),
+            tick_interval: DEFAULT_TICK_INTERVAL,
             executor,
         }
     }
 
+    /// Replaces the system clock, e.g. with a `ManualClock` in tests.
+    ///
+    /// Set the clock before `with_store`, which already uses it.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Persists the state to `store` and restores the state saved in it.
+    ///
+    /// Pending tasks are restored, except the overdue tasks of triggers with a
+    /// recorded last fire time: those are recomputed by the trigger's misfire
+    /// policy once the trigger is added again.
+    pub fn with_store(mut self, store: impl StateStore) -> io::Result<Self> {
+        let state = store.load()?;
+        let now = self.clock.now();
+
+        self.last_fired = state.last_fired.clone();
+        self.fire_counts = state.fire_counts;
+        self.recovered = state.last_fired;
+        let pending = state
+            .pending
+            .into_iter()
+            .filter(|task| task.run_at > now || !self.recovered.contains_key(&task.trigger_id))
+            .collect();
+        self.push_tasks(pending);
+
+        self.store = Some(Box::new(store));
+        Ok(self)
+    }
+
+    /// Sets the length of the rolling look-ahead window.
+    pub fn with_lookahead(mut self, lookahead: Duration) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    /// Sets how often the started actor ticks.
+    ///
+    /// The interval is measured in real time, independently of the clock:
+    /// a test on a `ManualClock` can use a short interval, or skip the actor
+    /// and call `tick()` directly.
+    pub fn with_tick_interval(mut self, tick_interval: Duration) -> Self {
+        self.tick_interval = tick_interval;
+        self
+    }
+
+    /// Sets how long ids of past tasks are remembered for deduplication.
+    ///
+    /// A task added again after its id was pruned is scheduled again.
+    pub fn with_dedup_retention(mut self, retention: Duration) -> Self {
+        self.dedup_retention = retention;
+        self
+    }
+
+    /// Limits how many tasks are dispatched per tick.
+    ///
+    /// Due tasks beyond the limit stay in the heap, earliest first, and are
+    /// dispatched on the following ticks.
+    pub fn with_dispatch_limit(mut self, limit: usize) -> Self {
+        self.dispatch_limit = Some(limit);
+        self
+    }
+
+    /// Adds a trigger, replacing any trigger with the same id.
+    ///
+    /// Planning starts from the time the trigger is added. If the store had
+    /// recorded a last fire time for the trigger, the firings missed since then
+    /// are queued according to the trigger's misfire policy. A replaced trigger
+    /// keeps its last fire time and fire count.
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.drop_pending(&trigger.id);
+        self.triggers.remove(&trigger.id);
+        let now = self.clock.now();
+
+        if let Some(last_fired) = self.recovered.remove(&trigger.id) {
+            let mut missed = Planner::plan_trigger(
+                &trigger,
+                last_fired + chrono::Duration::milliseconds(1),
+                now,
+                self.fired(&trigger.id),
+            );
+            match trigger.misfire_policy {
+                MisfirePolicy::FireAll => {}
+                MisfirePolicy::FireOnceNow => missed = missed.pop().into_iter().collect(),
+                MisfirePolicy::Skip => missed.clear(),
+            }
+            self.push_tasks(missed);
+        }
+
+        self.triggers.insert(
+            trigger.id.clone(),
+            TriggerState {
+                trigger,
+                watermark: now,
+                paused: false,
+            },
+        );
+        self.dirty = true;
+        self.plan(now);
+    }
+
+    /// Removes a trigger and drops its pending tasks.
+    ///
+    /// Returns `false` if the trigger does not exist.
+    pub fn remove_trigger(&mut self, trigger_id: &str) -> bool {
+        self.drop_pending(trigger_id);
+        self.last_fired.remove(trigger_id);
+        self.fire_counts.remove(trigger_id);
+        self.triggers.remove(trigger_id).is_some()
+    }
+
+    /// Pauses a trigger and drops its pending tasks.
+    ///
+    /// Returns `false` if the trigger does not exist.
+    pub fn pause_trigger(&mut self, trigger_id: &str) -> bool {
+        let Some(state) = self.triggers.get_mut(trigger_id) else {
+            return false;
+        };
+        state.paused = true;
+        self.drop_pending(trigger_id);
+        true
+    }
+
+    /// Resumes a paused trigger. Firings missed while paused are skipped.
+    ///
+    /// Returns `false` if the trigger does not exist.
+    pub fn resume_trigger(&mut self, trigger_id: &str) -> bool {
+        let Some(state) = self.triggers.get_mut(trigger_id) else {
+            return false;
+        };
+        if state.paused {
+            state.paused = false;
+            state.watermark = self.clock.now();
+            self.plan(self.clock.now());
+        }
+        true
+    }
+
+    /// Returns the ids of the triggers owned by the scheduler, sorted.
+    pub fn trigger_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.triggers.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Plans every active trigger up to `now + lookahead`.
+    ///
+    /// Each trigger is planned from its watermark, which then moves to the end
+    /// of the window, so repeated calls never queue the same firing twice.
+    pub fn plan(&mut self, now: DateTime<Utc>) {
+        let horizon = now + self.lookahead;
+        let mut planned = Vec::new();
+
+        for state in self.triggers.values() {
+            if state.paused || state.watermark >= horizon {
+                continue;
+            }
+            // 已执行和已入队的次数都计入 max_fires
+            let fired = match state.trigger.max_fires {
+                Some(_) => self.fired(&state.trigger.id) + self.pending_count(&state.trigger.id),
+                None => 0,
+            };
+            planned.extend(Planner::plan_trigger(
+                &state.trigger,
+                state.watermark,
+                horizon,
+                fired,
+            ));
+        }
+        for state in self.triggers.values_mut() {
+            if !state.paused {
+                state.watermark = state.watermark.max(horizon);
+            }
+        }
+
+        self.push_tasks(planned);
+    }
+
+    /// Previews the next `n` effective fire times of a trigger from now.
+    ///
+    /// Returns `None` if the trigger does not exist.
+    pub fn preview(&self, trigger_id: &str, n: usize) -> Option<Vec<DateTime<Utc>>> {
+        let state = self.triggers.get(trigger_id)?;
+        Some(
+            state
+                .trigger
+                .preview(self.clock.now(), n, self.fired(trigger_id)),
+        )
+    }
+
+    /// Returns how many times a trigger fired.
+    pub fn fired(&self, trigger_id: &str) -> u32 {
+        self.fire_counts.get(trigger_id).copied().unwrap_or(0)
+    }
+
+    /// Number of pending tasks of a trigger, including the firings queued
+    /// behind a running one.
+    fn pending_count(&self, trigger_id: &str) -> u32 {
+        let planned = self
+            .tasks
+            .iter()
+            .filter(|task| task.trigger_id == trigger_id)
+            .count();
+        let queued = self.queued.get(trigger_id).map_or(0, VecDeque::len);
+        (planned + queued) as u32
+    }
+
+    /// Returns the time of the last firing of a trigger.
+    pub fn last_fired(&self, trigger_id: &str) -> Option<DateTime<Utc>> {
+        self.last_fired.get(trigger_id).copied()
+    }
+
+    /// Saves the state to the store if it changed since the last save.
+    pub fn persist(&mut self) -> io::Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        store.save(&SchedulerState {
+            last_fired: self.last_fired.clone(),
+            fire_counts: self.fire_counts.clone(),
+            pending: self
+                .tasks
+                .iter()
+                .chain(self.queued.values().flatten())
+                .cloned()
+                .collect(),
+        })?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Persists the state, logging instead of failing.
+    fn save_state(&mut self) {
+        if let Err(err) = self.persist() {
+            eprintln!("Failed to persist scheduler state: {}", err);
+        }
+    }
+
+    /// Removes the pending tasks of a trigger from the heap.
+    ///
+    /// Their unique ids are forgotten as well, so the same firings can be
+    /// planned again once the trigger is resumed or re-added.
+    fn drop_pending(&mut self, trigger_id: &str) {
+        let unique_task_ids = &mut self.unique_task_ids;
+        self.dirty = true;
+        self.queued.remove(trigger_id);
+        self.tasks.retain(|task| {
+            if task.trigger_id != trigger_id {
+                return true;
+            }
+            unique_task_ids.remove(&task.id);
+            false
+        });
+    }
+
     /// Adds tasks generated by the `Planner` to the scheduler's task heap, ensuring idempotency.
     ///
     /// # Arguments
     ///
     /// * `tasks` - A vector of `Task` objects generated by the `Planner`.
     pub fn add_tasks(&mut self, tasks: Vec<Task>) {
-        for task in tasks {
-            let task_id = format!("{}-{}", task.trigger_id, task.run_at.timestamp_millis());
+        self.push_tasks(tasks);
+
+        // After adding tasks, run the scheduler to check if any task is ready to execute
+        self.run();
+    }
 
+    /// Pushes tasks onto the heap, skipping the ones already scheduled.
+    fn push_tasks(&mut self, tasks: Vec<Task>) {
+        for task in tasks {
             // Ensure idempotency: add only if the task is not already in the unique set
-            if self.unique_task_ids.contains(&task_id) {
-                println!("Task {} already scheduled, skipping.", task_id);
+            if self.unique_task_ids.contains_key(&task.id) {
+                println!("Task {} already scheduled, skipping.", task.id);
                 continue;
             }
 
             // Add task to the heap and record its unique ID
+            self.unique_task_ids.insert(task.id.clone(), task.run_at);
             self.tasks.push(task);
-            self.unique_task_ids.insert(task_id);
+            self.dirty = true;
         }
+    }
 
-        // After adding tasks, run the scheduler to check if any task is ready to execute
-        self.run();
+    /// Forgets the ids of tasks that ran longer than `dedup_retention` ago.
+    ///
+    /// Ids of tasks still pending are kept regardless of their run time.
+    pub fn prune_unique_task_ids(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - self.dedup_retention;
+        if self
+            .unique_task_ids
+            .values()
+            .all(|run_at| *run_at >= cutoff)
+        {
+            return;
+        }
+        let pending: HashSet<&str> = self
+            .tasks
+            .iter()
+            .chain(self.queued.values().flatten())
+            .map(|task| task.id.as_str())
+            .collect();
+        self.unique_task_ids
+            .retain(|id, run_at| *run_at >= cutoff || pending.contains(id.as_str()));
+    }
+
+    /// Number of task ids remembered for deduplication.
+    pub fn unique_task_count(&self) -> usize {
+        self.unique_task_ids.len()
     }
 }
 
 impl<E: Executor> Scheduler<E> {
     /// Checks if the next task is ready to execute and runs it if so.
+    ///
+    /// Stops once the per-tick dispatch limit is reached.
     pub fn run(&mut self) {
         while let Some(task) = self.tasks.peek() {
+            if self
+                .dispatch_limit
+                .is_some_and(|limit| self.dispatched >= limit)
+            {
+                break;
+            }
             // If the task is ready to run, execute it
-            if task.run_at <= Utc::now() {
+            if task.run_at <= self.clock.now() {
                 let task = self.tasks.pop().unwrap();
-                self.executor.execute(&task);
+                self.dispatched += 1;
+                self.dispatch(task);
             } else {
                 // If the next task is not ready, exit the loop
                 break;
             }
         }
     }
+
+    /// Starts a new tick: resets the dispatch budget, plans the look-ahead
+    /// window, runs the due tasks and persists the state.
+    pub fn tick(&mut self) {
+        self.dispatched = 0;
+        let now = self.clock.now();
+        self.prune_unique_task_ids(now);
+        self.plan(now);
+        self.run();
+        self.save_state();
+    }
+
+    /// Records that a run finished, starting the next queued firing of the trigger.
+    ///
+    /// Returns `false` if the run was not being tracked.
+    pub fn complete(&mut self, trigger_id: &str, task_id: &str) -> bool {
+        let Some(running) = self.running.get_mut(trigger_id) else {
+            return false;
+        };
+        let Some(index) = running.iter().position(|task| task.id == task_id) else {
+            return false;
+        };
+        running.remove(index);
+        if running.is_empty() {
+            self.running.remove(trigger_id);
+        }
+
+        // 排队的触发在上一次运行完成后执行
+        if !self.is_running(trigger_id) {
+            if let Some(next) = self
+                .queued
+                .get_mut(trigger_id)
+                .and_then(VecDeque::pop_front)
+            {
+                self.execute(next, true);
+            }
+            if self.queued.get(trigger_id).is_some_and(VecDeque::is_empty) {
+                self.queued.remove(trigger_id);
+            }
+        }
+        true
+    }
+
+    /// Returns whether a run of the trigger is still in progress.
+    pub fn is_running(&self, trigger_id: &str) -> bool {
+        self.running.contains_key(trigger_id)
+    }
+
+    /// Executes a due task according to its trigger's overlap policy.
+    fn dispatch(&mut self, task: Task) {
+        let policy = self
+            .triggers
+            .get(&task.trigger_id)
+            .map_or(OverlapPolicy::Allow, |state| state.trigger.overlap_policy);
+        let running = self.is_running(&task.trigger_id);
+
+        match policy {
+            OverlapPolicy::Skip if running => {
+                println!(
+                    "Trigger {} is still running, skipping task {}",
+                    task.trigger_id, task.id
+                );
+                self.last_fired.insert(task.trigger_id.clone(), task.run_at);
+                self.dirty = true;
+                return;
+            }
+            OverlapPolicy::Queue if running => {
+                self.queued
+                    .entry(task.trigger_id.clone())
+                    .or_default()
+                    .push_back(task);
+                self.dirty = true;
+                return;
+            }
+            OverlapPolicy::CancelPrevious => {
+                for previous in self.running.remove(&task.trigger_id).unwrap_or_default() {
+                    self.executor.cancel(&previous);
+                }
+            }
+            _ => {}
+        }
+
+        self.execute(task, policy != OverlapPolicy::Allow);
+    }
+
+    /// Executes a task and records the firing.
+    fn execute(&mut self, task: Task, track: bool) {
+        self.executor.execute(&task);
+        self.last_fired.insert(task.trigger_id.clone(), task.run_at);
+        *self.fire_counts.entry(task.trigger_id.clone()).or_default() += 1;
+        self.dirty = true;
+        if track {
+            self.running
+                .entry(task.trigger_id.clone())
+                .or_default()
+                .push(task);
+        }
+    }
 }
 
 /// Actix actor implementation for Scheduler.
 impl<E: Executor + std::marker::Unpin> Actor for Scheduler<E> {
     type Context = Context<Self>;
 
-    /// Starts the Tick message loop with a fixed interval.
+    /// Starts the Tick message loop, ticking every `tick_interval`.
+    ///
+    /// Every tick plans the look-ahead window and then runs the due tasks.
     fn started(&mut self, ctx: &mut Self::Context) {
-        ctx.run_interval(Duration::from_secs(1), |scheduler, _ctx| {
-            scheduler.run();
+        ctx.run_interval(self.tick_interval, |scheduler, _ctx| {
+            scheduler.tick();
         });
     }
 }
@@ -99,12 +600,104 @@ impl<E: Executor + std::marker::Unpin> Handler<AddTasks> for Scheduler<E> {
 
     fn handle(&mut self, msg: AddTasks, _ctx: &mut Self::Context) {
         self.add_tasks(msg.0); // Use the `add_tasks` method to add tasks from the message
+        self.save_state();
+    }
+}
+
+/// Message type for adding a trigger to the Scheduler.
+pub struct AddTrigger(pub Trigger);
+
+impl Message for AddTrigger {
+    type Result = ();
+}
+
+impl<E: Executor + std::marker::Unpin> Handler<AddTrigger> for Scheduler<E> {
+    type Result = ();
+
+    fn handle(&mut self, msg: AddTrigger, _ctx: &mut Self::Context) {
+        self.add_trigger(msg.0);
+        self.run();
+        self.save_state();
+    }
+}
+
+/// Message type for removing a trigger from the Scheduler by id.
+pub struct RemoveTrigger(pub String);
+
+impl Message for RemoveTrigger {
+    type Result = bool;
+}
+
+impl<E: Executor + std::marker::Unpin> Handler<RemoveTrigger> for Scheduler<E> {
+    type Result = bool;
+
+    fn handle(&mut self, msg: RemoveTrigger, _ctx: &mut Self::Context) -> bool {
+        let removed = self.remove_trigger(&msg.0);
+        self.save_state();
+        removed
+    }
+}
+
+/// Message type for pausing a trigger by id.
+pub struct PauseTrigger(pub String);
+
+impl Message for PauseTrigger {
+    type Result = bool;
+}
+
+impl<E: Executor + std::marker::Unpin> Handler<PauseTrigger> for Scheduler<E> {
+    type Result = bool;
+
+    fn handle(&mut self, msg: PauseTrigger, _ctx: &mut Self::Context) -> bool {
+        let paused = self.pause_trigger(&msg.0);
+        self.save_state();
+        paused
+    }
+}
+
+/// Message type for resuming a paused trigger by id.
+pub struct ResumeTrigger(pub String);
+
+impl Message for ResumeTrigger {
+    type Result = bool;
+}
+
+impl<E: Executor + std::marker::Unpin> Handler<ResumeTrigger> for Scheduler<E> {
+    type Result = bool;
+
+    fn handle(&mut self, msg: ResumeTrigger, _ctx: &mut Self::Context) -> bool {
+        let resumed = self.resume_trigger(&msg.0);
+        self.run();
+        self.save_state();
+        resumed
+    }
+}
+
+/// Message type reporting that a run started by the Scheduler finished.
+pub struct RunCompleted {
+    pub trigger_id: String,
+    pub task_id: String,
+}
+
+impl Message for RunCompleted {
+    type Result = bool;
+}
+
+impl<E: Executor + std::marker::Unpin> Handler<RunCompleted> for Scheduler<E> {
+    type Result = bool;
+
+    fn handle(&mut self, msg: RunCompleted, _ctx: &mut Self::Context) -> bool {
+        let completed = self.complete(&msg.trigger_id, &msg.task_id);
+        self.save_state();
+        completed
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use crate::store::MemoryStore;
     use chrono::Utc;
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
@@ -132,53 +725,56 @@ mod tests {
             log.push(task.id.clone());
             println!("Executing Task ID: {}", task.id);
         }
+
+        fn cancel(&self, task: &Task) {
+            self.log.lock().unwrap().push(format!("cancel:{}", task.id));
+        }
     }
 
-    #[actix_rt::test]
-    async fn test_empty_queue() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let _scheduler = Scheduler::new(executor).start();
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    /// A scheduler whose clock only moves when the test advances it.
+    fn manual_scheduler() -> (Scheduler<LogExecutor>, ManualClock) {
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let scheduler = Scheduler::new(LogExecutor::new()).with_clock(clock.clone());
+        (scheduler, clock)
+    }
 
-        // 等待 1 秒，验证空队列不应执行任何任务
-        actix::clock::sleep(Duration::from_secs(1)).await;
+    #[test]
+    fn test_empty_queue() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        // 检查日志应为空
-        let log_entries = log.lock().unwrap();
+        // 空队列不应执行任何任务
+        clock.advance(chrono::Duration::seconds(1));
+        scheduler.tick();
         assert!(
-            log_entries.is_empty(),
+            scheduler.executor.get_log().is_empty(),
             "No tasks should be executed for an empty queue."
         );
     }
 
-    #[actix_rt::test]
-    async fn test_immediate_execution() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
-
-        let now = Utc::now();
-        let tasks = vec![Task::new("task_immediate", now, "workflow1", "trigger1")];
+    #[test]
+    fn test_immediate_execution() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        scheduler.do_send(AddTasks(tasks));
-
-        // 等待 1 秒确保任务执行
-        actix::clock::sleep(Duration::from_secs(1)).await;
-
-        // 检查日志，验证任务立即执行
-        let log_entries = log.lock().unwrap();
-        assert_eq!(log_entries.len(), 1);
-        assert_eq!(log_entries[0], "task_immediate");
+        // 已到期的任务在加入时立即执行
+        scheduler.add_tasks(vec![Task::new(
+            "task_immediate",
+            clock.now(),
+            "workflow1",
+            "trigger1",
+        )]);
+        assert_eq!(scheduler.executor.get_log(), vec!["task_immediate"]);
     }
 
-    #[actix_rt::test]
-    async fn test_add_tasks_and_run() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
+    #[test]
+    fn test_add_tasks_and_run() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        let now = Utc::now();
-        let tasks = vec![
+        let now = clock.now();
+        scheduler.add_tasks(vec![
             Task::new(
                 "task1",
                 now + Duration::from_secs(1),
@@ -197,87 +793,72 @@ mod tests {
                 "workflow1",
                 "trigger1",
             ), // Duplicate task for idempotency
-        ];
-
-        scheduler.do_send(AddTasks(tasks));
+        ]);
 
-        // 等待 3 秒，确保所有任务有时间执行
-        actix::clock::sleep(Duration::from_secs(3)).await;
+        clock.advance(chrono::Duration::seconds(1));
+        scheduler.tick();
+        assert_eq!(scheduler.executor.get_log(), vec!["task1"]);
 
-        // 检查日志，验证仅唯一任务被执行
-        let log_entries = log.lock().unwrap();
-        assert_eq!(log_entries.len(), 2);
-        assert!(log_entries.contains(&"task1".to_string()));
-        assert!(log_entries.contains(&"task2".to_string()));
+        // 仅唯一任务被执行
+        clock.advance(chrono::Duration::seconds(1));
+        scheduler.tick();
+        assert_eq!(scheduler.executor.get_log(), vec!["task1", "task2"]);
     }
 
-    #[actix_rt::test]
-    async fn test_future_task_not_executed_early() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
+    #[test]
+    fn test_future_task_not_executed_early() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        let now = Utc::now();
-        let tasks = vec![Task::new(
+        scheduler.add_tasks(vec![Task::new(
             "task_future",
-            now + Duration::from_secs(10),
+            clock.now() + Duration::from_secs(10),
             "workflow1",
             "trigger1",
-        )];
-
-        scheduler.do_send(AddTasks(tasks));
-
-        // 等待 5 秒，不应执行未来任务
-        actix::clock::sleep(Duration::from_secs(5)).await;
+        )]);
 
-        // 检查日志，确保任务未提前执行
-        let log_entries = log.lock().unwrap();
+        // 5 秒后仍未到期
+        clock.advance(chrono::Duration::seconds(5));
+        scheduler.tick();
         assert!(
-            log_entries.is_empty(),
+            scheduler.executor.get_log().is_empty(),
             "Future tasks should not execute before their scheduled time."
         );
-    }
 
-    #[actix_rt::test]
-    async fn test_high_frequency_scheduling() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
-
-        let now = Utc::now();
-        let mut tasks = vec![];
-
-        for i in 0..10 {
-            let task_id = format!("task_{}", i);
-            tasks.push(Task::new(
-                &task_id,
-                now + Duration::from_millis(i * 100),
-                "workflow1",
-                "trigger1",
-            ));
-        }
+        clock.advance(chrono::Duration::seconds(5));
+        scheduler.tick();
+        assert_eq!(scheduler.executor.get_log(), vec!["task_future"]);
+    }
 
-        scheduler.do_send(AddTasks(tasks));
+    #[test]
+    fn test_high_frequency_scheduling() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        // 等待 2 秒，确保所有任务有时间执行
-        actix::clock::sleep(Duration::from_secs(2)).await;
+        let now = clock.now();
+        let tasks = (0..10)
+            .map(|i| {
+                Task::new(
+                    &format!("task_{}", i),
+                    now + Duration::from_millis(i * 100),
+                    "workflow1",
+                    "trigger1",
+                )
+            })
+            .collect();
+        scheduler.add_tasks(tasks);
 
-        // 检查日志，确保所有任务按顺序执行
-        let log_entries = log.lock().unwrap();
-        assert_eq!(log_entries.len(), 10);
-        for i in 0..10 {
-            assert_eq!(log_entries[i], format!("task_{}", i));
-        }
+        // 所有任务按时间顺序执行
+        clock.advance(chrono::Duration::seconds(1));
+        scheduler.tick();
+        let expected: Vec<String> = (0..10).map(|i| format!("task_{}", i)).collect();
+        assert_eq!(scheduler.executor.get_log(), expected);
     }
 
-    #[actix_rt::test]
-    async fn test_duplicate_task_handling() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
+    #[test]
+    fn test_duplicate_task_handling() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        let now = Utc::now();
-        let tasks = vec![
+        let now = clock.now();
+        scheduler.add_tasks(vec![
             Task::new(
                 "task1",
                 now + Duration::from_secs(1),
@@ -296,28 +877,23 @@ mod tests {
                 "workflow2",
                 "trigger2",
             ),
-        ];
+        ]);
 
-        scheduler.do_send(AddTasks(tasks));
-
-        // 等待 3 秒，确保所有任务有时间执行
-        actix::clock::sleep(Duration::from_secs(3)).await;
-
-        // 检查日志，验证仅唯一任务被执行
-        let log_entries = log.lock().unwrap();
-        assert_eq!(log_entries.len(), 2);
-        assert!(log_entries.contains(&"task1".to_string()));
-        assert!(log_entries.contains(&"task2".to_string()));
+        // 仅唯一任务被执行
+        clock.advance(chrono::Duration::seconds(1));
+        scheduler.tick();
+        let log = scheduler.executor.get_log();
+        assert_eq!(log.len(), 2);
+        assert!(log.contains(&"task1".to_string()));
+        assert!(log.contains(&"task2".to_string()));
     }
 
-    #[actix_rt::test]
-    async fn test_task_execution_order() {
-        let executor = LogExecutor::new();
-        let log = executor.log.clone();
-        let scheduler = Scheduler::new(executor).start();
+    #[test]
+    fn test_task_execution_order() {
+        let (mut scheduler, clock) = manual_scheduler();
 
-        let now = Utc::now();
-        let tasks = vec![
+        let now = clock.now();
+        scheduler.add_tasks(vec![
             Task::new(
                 "task1",
                 now + Duration::from_secs(2),
@@ -336,21 +912,538 @@ mod tests {
                 "workflow3",
                 "trigger3",
             ),
-        ];
+        ]);
 
-        scheduler.do_send(AddTasks(tasks));
+        // 一次推进 3 秒，任务仍按 run_at 顺序执行
+        clock.advance(chrono::Duration::seconds(3));
+        scheduler.tick();
+        assert_eq!(
+            scheduler.executor.get_log(),
+            vec!["task2", "task1", "task3"]
+        );
+    }
+
+    /// Ids of the pending tasks, asserting that none is queued twice.
+    fn pending_ids<E: Executor>(scheduler: &Scheduler<E>) -> Vec<String> {
+        let ids: Vec<String> = scheduler.tasks.iter().map(|task| task.id.clone()).collect();
+        let unique: HashSet<&String> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len(), "A firing was queued twice");
+        ids
+    }
+
+    #[test]
+    fn test_plan_queues_each_firing_once() {
+        let (scheduler, clock) = manual_scheduler();
+        let mut scheduler = scheduler.with_lookahead(Duration::from_secs(10));
+        scheduler.add_trigger(Trigger::new("trigger1", "* * * * * *", "workflow1"));
+        // 整个窗口 [00:00:00, 00:00:10) 都已规划
+        assert_eq!(pending_ids(&scheduler).len(), 10);
+
+        // 重复规划同一个窗口不会重复入队
+        let now = clock.now();
+        scheduler.plan(now);
+        scheduler.plan(now);
+        assert_eq!(pending_ids(&scheduler).len(), 10);
+
+        // 窗口向前滚动时只补充新的触发时间
+        scheduler.plan(now + chrono::Duration::seconds(5));
+        assert_eq!(pending_ids(&scheduler).len(), 15);
+    }
+
+    #[test]
+    fn test_pause_resume_and_remove_trigger() {
+        let (scheduler, clock) = manual_scheduler();
+        let mut scheduler = scheduler.with_lookahead(Duration::from_secs(10));
+        scheduler.add_trigger(Trigger::new("trigger1", "* * * * * *", "workflow1"));
+        scheduler.add_trigger(Trigger::new("trigger2", "* * * * * *", "workflow2"));
+        assert_eq!(scheduler.trigger_ids(), vec!["trigger1", "trigger2"]);
+
+        // 暂停后丢弃已规划的任务，也不再规划
+        assert!(scheduler.pause_trigger("trigger1"));
+        scheduler.plan(clock.now());
+        assert!(scheduler
+            .tasks
+            .iter()
+            .all(|task| task.trigger_id == "trigger2"));
+
+        // 恢复后重新规划
+        assert!(scheduler.resume_trigger("trigger1"));
+        pending_ids(&scheduler);
+        assert!(scheduler
+            .tasks
+            .iter()
+            .any(|task| task.trigger_id == "trigger1"));
+
+        assert!(scheduler.remove_trigger("trigger1"));
+        assert!(!scheduler.remove_trigger("trigger1"));
+        assert!(!scheduler.pause_trigger("trigger1"));
+        assert!(scheduler
+            .tasks
+            .iter()
+            .all(|task| task.trigger_id == "trigger2"));
+        assert_eq!(scheduler.trigger_ids(), vec!["trigger2"]);
+    }
 
-        // 等待 4 秒以确保任务按顺序执行
-        actix::clock::sleep(Duration::from_secs(4)).await;
+    #[actix_rt::test]
+    async fn test_trigger_messages() {
+        let executor = LogExecutor::new();
+        let log = executor.log.clone();
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let scheduler = Scheduler::new(executor)
+            .with_clock(clock.clone())
+            .with_lookahead(Duration::from_secs(2))
+            .with_tick_interval(Duration::from_millis(10))
+            .start();
+
+        scheduler
+            .send(AddTrigger(Trigger::new(
+                "trigger1",
+                "* * * * * *",
+                "workflow1",
+            )))
+            .await
+            .unwrap();
+
+        // 推进 2 秒后的下一次 tick，触发器按秒执行且每次只执行一次
+        clock.advance(chrono::Duration::seconds(2));
+        actix::clock::sleep(Duration::from_millis(50)).await;
+        {
+            let log_entries = log.lock().unwrap();
+            let unique: HashSet<&String> = log_entries.iter().collect();
+            assert_eq!(log_entries.len(), 3);
+            assert_eq!(unique.len(), log_entries.len());
+        }
+
+        // 暂停后不再执行
+        assert!(scheduler
+            .send(PauseTrigger("trigger1".to_string()))
+            .await
+            .unwrap());
+        clock.advance(chrono::Duration::seconds(2));
+        actix::clock::sleep(Duration::from_millis(50)).await;
+        assert_eq!(log.lock().unwrap().len(), 3);
+
+        assert!(scheduler
+            .send(RemoveTrigger("trigger1".to_string()))
+            .await
+            .unwrap());
+        assert!(!scheduler
+            .send(ResumeTrigger("trigger1".to_string()))
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let store = MemoryStore::new();
+        let (scheduler, clock) = manual_scheduler();
+        let now = clock.now();
+        let future = now + chrono::Duration::seconds(30);
+
+        let mut scheduler = scheduler.with_store(store.clone()).unwrap();
+        scheduler.add_tasks(vec![
+            Task::new("task_due", now, "workflow1", "trigger1"),
+            Task::new("task_future", future, "workflow1", "trigger1"),
+        ]);
+        scheduler.persist().unwrap();
+
+        let state = store.load().unwrap();
+        assert_eq!(state.last_fired.get("trigger1"), Some(&now));
+        assert_eq!(state.pending.len(), 1);
+        assert_eq!(state.pending[0].id, "task_future");
+
+        // 重启后恢复上次的触发时间和未执行的任务
+        let restarted = Scheduler::new(LogExecutor::new())
+            .with_clock(clock)
+            .with_store(store.clone())
+            .unwrap();
+        assert_eq!(restarted.last_fired("trigger1"), Some(now));
+        assert_eq!(pending_ids(&restarted), vec!["task_future"]);
+    }
+
+    /// Restarts a scheduler whose every-second trigger last fired 10.5 seconds
+    /// ago and returns how many missed firings were executed.
+    fn missed_firings(policy: MisfirePolicy) -> usize {
+        let store = MemoryStore::new();
+        let last_fired = utc("2023-12-31T23:59:50Z");
+        store
+            .save(&SchedulerState {
+                last_fired: HashMap::from([("trigger1".to_string(), last_fired)]),
+                pending: vec![Task::new("stale", last_fired, "workflow1", "trigger1")],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let executor = LogExecutor::new();
+        let log = executor.log.clone();
+        // 重启在两次触发之间，下一次触发还没有到期
+        let mut scheduler = Scheduler::new(executor)
+            .with_clock(ManualClock::new(utc("2024-01-01T00:00:00.500Z")))
+            .with_lookahead(Duration::from_secs(5))
+            .with_store(store)
+            .unwrap();
+        scheduler.add_trigger(
+            Trigger::new("trigger1", "* * * * * *", "workflow1").with_misfire_policy(policy),
+        );
+        scheduler.run();
 
         let log_entries = log.lock().unwrap();
+        assert!(!log_entries.contains(&"stale".to_string()));
+        log_entries.len()
+    }
+
+    #[test]
+    fn test_misfire_policies() {
+        // 错过了 23:59:51 到 00:00:00 的 10 次触发
+        assert_eq!(missed_firings(MisfirePolicy::FireAll), 10);
+        assert_eq!(missed_firings(MisfirePolicy::FireOnceNow), 1);
+        assert_eq!(missed_firings(MisfirePolicy::Skip), 0);
+    }
+
+    #[test]
+    fn test_max_fires_counts_executed_and_queued_firings() {
+        let store = MemoryStore::new();
+        store
+            .save(&SchedulerState {
+                fire_counts: HashMap::from([("trigger1".to_string(), 2)]),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (scheduler, clock) = manual_scheduler();
+        let mut scheduler = scheduler
+            .with_lookahead(Duration::from_secs(10))
+            .with_store(store)
+            .unwrap();
+        let anchor = clock.now();
+        scheduler.add_trigger(
+            Trigger::interval(
+                "trigger1",
+                chrono::Duration::seconds(1),
+                anchor,
+                "workflow1",
+            )
+            .with_max_fires(5),
+        );
+        scheduler.add_trigger(
+            Trigger::once(
+                "trigger2",
+                anchor + chrono::Duration::seconds(3),
+                "workflow2",
+            )
+            .with_max_fires(5),
+        );
+        assert_eq!(scheduler.fired("trigger1"), 2);
+        assert_eq!(scheduler.preview("trigger1", 10).unwrap().len(), 3);
+        assert!(scheduler.preview("missing", 10).is_none());
+        assert_eq!(scheduler.pending_count("trigger1"), 3);
+        assert_eq!(scheduler.pending_count("trigger2"), 1);
+
+        // 窗口继续滚动也不会超过上限
+        scheduler.plan(anchor + chrono::Duration::seconds(30));
+        assert_eq!(scheduler.pending_count("trigger1"), 3);
+        assert_eq!(scheduler.pending_count("trigger2"), 1);
+        pending_ids(&scheduler);
+
+        // 排队等待上一次运行完成的触发同样计入上限
+        let (mut scheduler, clock) = manual_scheduler();
+        scheduler.add_trigger(
+            Trigger::interval(
+                "trigger3",
+                chrono::Duration::seconds(1),
+                clock.now(),
+                "workflow3",
+            )
+            .with_max_fires(4)
+            .with_overlap_policy(OverlapPolicy::Queue),
+        );
+        clock.advance(chrono::Duration::seconds(10));
+        scheduler.tick();
+        assert_eq!(scheduler.executor.get_log().len(), 1);
+        assert_eq!(scheduler.queued["trigger3"].len(), 3);
+        assert!(scheduler.tasks.is_empty());
+
+        clock.advance(chrono::Duration::seconds(10));
+        scheduler.tick();
+        assert!(scheduler.tasks.is_empty());
+        assert_eq!(scheduler.pending_count("trigger3"), 3);
+
+        // 每次运行完成后执行下一个排队的触发, 总数不超过上限
+        for _ in 0..3 {
+            let running = scheduler.running["trigger3"][0].id.clone();
+            assert!(scheduler.complete("trigger3", &running));
+            scheduler.tick();
+        }
+        assert_eq!(scheduler.executor.get_log().len(), 4);
+        assert_eq!(scheduler.fired("trigger3"), 4);
+        assert_eq!(scheduler.pending_count("trigger3"), 0);
+        assert!(scheduler.tasks.is_empty());
+    }
+
+    /// Fires three overlapping tasks of a trigger with the given overlap
+    /// policy, completing the first run in between, and returns the log.
+    fn overlapping_runs(policy: OverlapPolicy) -> Vec<String> {
+        let executor = LogExecutor::new();
+        let log = executor.log.clone();
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let mut scheduler = Scheduler::new(executor).with_clock(clock.clone());
+        let far_future = clock.now() + chrono::Duration::days(1);
+        scheduler.add_trigger(
+            Trigger::once("trigger1", far_future, "workflow1").with_overlap_policy(policy),
+        );
+
+        let now = clock.now() - chrono::Duration::seconds(1);
+        scheduler.add_tasks(vec![Task::new("run1", now, "workflow1", "trigger1")]);
+        scheduler.add_tasks(vec![Task::new(
+            "run2",
+            now + chrono::Duration::milliseconds(1),
+            "workflow1",
+            "trigger1",
+        )]);
+        scheduler.complete("trigger1", "run1");
+        scheduler.add_tasks(vec![Task::new(
+            "run3",
+            now + chrono::Duration::milliseconds(2),
+            "workflow1",
+            "trigger1",
+        )]);
+
+        let entries = log.lock().unwrap().clone();
+        entries
+    }
+
+    #[test]
+    fn test_overlap_policies() {
+        assert_eq!(
+            overlapping_runs(OverlapPolicy::Allow),
+            vec!["run1", "run2", "run3"]
+        );
+        // run2 在 run1 运行时被跳过，run3 在 run1 完成后执行
+        assert_eq!(overlapping_runs(OverlapPolicy::Skip), vec!["run1", "run3"]);
+        // run2 等 run1 完成后执行，run3 继续排队
+        assert_eq!(overlapping_runs(OverlapPolicy::Queue), vec!["run1", "run2"]);
+        // run2 取消 run1，run1 已不在运行，run3 再取消 run2
+        assert_eq!(
+            overlapping_runs(OverlapPolicy::CancelPrevious),
+            vec!["run1", "cancel:run1", "run2", "cancel:run2", "run3"]
+        );
+    }
+
+    /// 执行后立即通过消息报告完成的执行器
+    struct CompletingExecutor {
+        scheduler: Recipient<RunCompleted>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Executor for CompletingExecutor {
+        fn execute(&self, task: &Task) {
+            self.log.lock().unwrap().push(task.id.clone());
+            self.scheduler.do_send(RunCompleted {
+                trigger_id: task.trigger_id.clone(),
+                task_id: task.id.clone(),
+            });
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_run_completed_message() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let executor_log = log.clone();
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let now = clock.now();
+        let scheduler = Scheduler::create(move |ctx| {
+            Scheduler::new(CompletingExecutor {
+                scheduler: ctx.address().recipient(),
+                log: executor_log,
+            })
+            .with_clock(clock)
+        });
+
+        let far_future = now + chrono::Duration::days(1);
+        scheduler
+            .send(AddTrigger(
+                Trigger::once("trigger1", far_future, "workflow1")
+                    .with_overlap_policy(OverlapPolicy::Skip),
+            ))
+            .await
+            .unwrap();
+
+        // 每次运行都已报告完成，后续触发不会被跳过。
+        // 邮箱按顺序处理，RunCompleted 总在下一条 AddTasks 之前处理
+        let now = now - chrono::Duration::seconds(1);
+        for i in 0..3 {
+            scheduler
+                .send(AddTasks(vec![Task::new(
+                    &format!("run{}", i),
+                    now + chrono::Duration::milliseconds(i),
+                    "workflow1",
+                    "trigger1",
+                )]))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(*log.lock().unwrap(), vec!["run0", "run1", "run2"]);
+    }
+
+    #[test]
+    fn test_dispatch_limit_per_tick() {
+        let executor = LogExecutor::new();
+        let log = executor.log.clone();
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let mut scheduler = Scheduler::new(executor)
+            .with_clock(clock.clone())
+            .with_dispatch_limit(2);
+
+        let now = clock.now() - chrono::Duration::seconds(1);
+        scheduler.add_tasks(
+            (0..5)
+                .map(|i| {
+                    Task::new(
+                        &format!("task{}", i),
+                        now + chrono::Duration::milliseconds(i),
+                        "workflow1",
+                        &format!("trigger{}", i),
+                    )
+                })
+                .collect(),
+        );
+        assert_eq!(log.lock().unwrap().len(), 2);
+
+        // 每个 tick 最多再派发两个，按时间先后
+        scheduler.tick();
+        assert_eq!(log.lock().unwrap().len(), 4);
+        scheduler.tick();
         assert_eq!(
-            *log_entries,
+            *log.lock().unwrap(),
+            vec!["task0", "task1", "task2", "task3", "task4"]
+        );
+    }
+
+    #[test]
+    fn test_jitter_spreads_planned_tasks() {
+        let (scheduler, _clock) = manual_scheduler();
+        let mut scheduler = scheduler.with_lookahead(Duration::from_secs(120));
+        for i in 0..20 {
+            scheduler.add_trigger(
+                Trigger::new(&format!("trigger{}", i), "0 * * * * *", "workflow1")
+                    .with_jitter(chrono::Duration::seconds(30)),
+            );
+        }
+
+        // 00:00 和 00:01 的触发各 20 个，同一分钟的触发被分散到 30 秒内
+        assert_eq!(pending_ids(&scheduler).len(), 40);
+        let spread: HashSet<_> = scheduler
+            .tasks
+            .iter()
+            .filter(|task| task.run_at < utc("2024-01-01T00:00:30Z"))
+            .map(|task| task.run_at)
+            .collect();
+        assert_eq!(spread.len(), 20);
+
+        // 每个触发器的偏移不变
+        for task in scheduler.tasks.iter() {
+            let jitter = scheduler.triggers[&task.trigger_id].trigger.jitter();
+            let minute = if task.run_at < utc("2024-01-01T00:01:00Z") {
+                utc("2024-01-01T00:00:00Z")
+            } else {
+                utc("2024-01-01T00:01:00Z")
+            };
+            assert_eq!(task.run_at, minute + jitter);
+        }
+    }
+
+    #[test]
+    fn test_dedup_set_is_pruned() {
+        let (scheduler, clock) = manual_scheduler();
+        let mut scheduler = scheduler.with_dedup_retention(Duration::from_secs(60));
+        let now = clock.now();
+        let old = now - chrono::Duration::minutes(10);
+        scheduler.add_tasks(vec![
+            Task::new("old", old, "workflow1", "trigger1"),
+            Task::new(
+                "recent",
+                now - chrono::Duration::seconds(1),
+                "workflow1",
+                "trigger1",
+            ),
+            Task::new(
+                "future",
+                now + chrono::Duration::minutes(5),
+                "workflow1",
+                "trigger1",
+            ),
+        ]);
+        assert_eq!(scheduler.unique_task_count(), 3);
+
+        // 超过保留期的已执行任务被遗忘，未执行的任务始终保留
+        scheduler.prune_unique_task_ids(now);
+        assert_eq!(scheduler.unique_task_count(), 2);
+        scheduler.prune_unique_task_ids(now + chrono::Duration::hours(1));
+        assert_eq!(scheduler.unique_task_count(), 1);
+        assert_eq!(pending_ids(&scheduler), vec!["future"]);
+    }
+
+    #[test]
+    fn test_same_instant_tasks_are_distinct() {
+        // 同一时刻触发的不同工作流都会被调度
+        let executor = LogExecutor::new();
+        let log = executor.log.clone();
+        let clock = ManualClock::new(utc("2024-01-01T00:00:00Z"));
+        let mut scheduler = Scheduler::new(executor).with_clock(clock.clone());
+        let now = clock.now() - chrono::Duration::seconds(1);
+        scheduler.add_tasks(vec![
+            Task::new(
+                &Task::canonical_id("trigger2", now),
+                now,
+                "workflow2",
+                "trigger2",
+            ),
+            Task::new(
+                &Task::canonical_id("trigger1", now),
+                now,
+                "workflow1",
+                "trigger1",
+            ),
+            Task::new(
+                &Task::canonical_id("trigger1", now),
+                now,
+                "workflow1",
+                "trigger1",
+            ),
+        ]);
+
+        assert_eq!(
+            *log.lock().unwrap(),
             vec![
-                "task2".to_string(),
-                "task1".to_string(),
-                "task3".to_string()
+                Task::canonical_id("trigger1", now),
+                Task::canonical_id("trigger2", now),
             ]
         );
     }
+
+    #[test]
+    fn test_manual_clock_drives_tick() {
+        let clock = ManualClock::new(utc("2024-01-01T00:00:30Z"));
+        let mut scheduler = Scheduler::new(LogExecutor::new()).with_clock(clock.clone());
+        scheduler.add_trigger(Trigger::new("trigger1", "0 * * * * *", "workflow1"));
+
+        // 时间未到整分，不应执行
+        scheduler.tick();
+        assert!(scheduler.executor.get_log().is_empty());
+
+        // 推进到整分，执行一次
+        clock.advance(chrono::Duration::seconds(30));
+        scheduler.tick();
+        assert_eq!(
+            scheduler.executor.get_log(),
+            vec![Task::canonical_id("trigger1", utc("2024-01-01T00:01:00Z"))]
+        );
+
+        // 一次推进 10 分钟，补上期间的每次触发
+        clock.advance(chrono::Duration::minutes(10));
+        scheduler.tick();
+        assert_eq!(scheduler.executor.get_log().len(), 11);
+        assert_eq!(scheduler.last_fired("trigger1"), Some(clock.now()));
+    }
 }
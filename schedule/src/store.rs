@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::task::Task;
+
+/// The scheduler state that survives a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchedulerState {
+    /// Time of the last firing of each trigger, keyed by trigger id.
+    pub last_fired: HashMap<String, DateTime<Utc>>,
+
+    /// Number of firings of each trigger, keyed by trigger id.
+    #[serde(default)]
+    pub fire_counts: HashMap<String, u32>,
+
+    /// Tasks that were scheduled but not yet executed.
+    pub pending: Vec<Task>,
+}
+
+/// Storage for the scheduler state.
+pub trait StateStore: Send + Sync + 'static {
+    /// Loads the saved state, or the default state if nothing was saved yet.
+    fn load(&self) -> io::Result<SchedulerState>;
+
+    /// Replaces the saved state.
+    fn save(&self, state: &SchedulerState) -> io::Result<()>;
+}
+
+/// Stores the scheduler state as a JSON file.
+///
+/// The state is written to a temporary file first and then renamed over the
+/// old one, so a crash during saving never leaves a half-written file.
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+impl JsonFileStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl StateStore for JsonFileStore {
+    fn load(&self) -> io::Result<SchedulerState> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(SchedulerState::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, state: &SchedulerState) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(&serde_json::to_vec(state)?)?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+/// Keeps the scheduler state in memory. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    state: Arc<Mutex<SchedulerState>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn load(&self) -> io::Result<SchedulerState> {
+        Ok(self.state.lock().unwrap().clone())
+    }
+
+    fn save(&self, state: &SchedulerState) -> io::Result<()> {
+        *self.state.lock().unwrap() = state.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_file_store_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "schedule-state-{}.json",
+            Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        let store = JsonFileStore::new(&path);
+
+        // 文件不存在时返回空状态
+        let state = store.load().unwrap();
+        assert!(state.last_fired.is_empty() && state.pending.is_empty());
+
+        let now = Utc::now();
+        let state = SchedulerState {
+            last_fired: HashMap::from([("trigger1".to_string(), now)]),
+            fire_counts: HashMap::from([("trigger1".to_string(), 3)]),
+            pending: vec![Task::new("task1", now, "workflow1", "trigger1")],
+        };
+        store.save(&state).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.last_fired, state.last_fired);
+        assert_eq!(loaded.fire_counts, state.fire_counts);
+        assert_eq!(loaded.pending[0].id, "task1");
+        assert_eq!(loaded.pending[0].run_at, now);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
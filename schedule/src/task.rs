@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 /// A scheduled task to be executed at a specific time.
 ///
@@ -9,8 +10,9 @@ use chrono::{DateTime, Utc};
 ///
 /// This struct supports ordering by execution time, allowing it to be 
 /// used in sorted collections such as `BinaryHeap` for task scheduling 
-/// where earlier tasks are prioritized.
-#[derive(Debug, Eq)]
+/// where earlier tasks are prioritized. A task's identity is `(run_at, id)`:
+/// equality and ordering both take the id into account.
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct Task {
     /// Unique identifier for the task.
     pub id: String,
@@ -34,11 +36,20 @@ impl Task {
             trigger_id: trigger_id.to_string(),
         }
     }
+
+    /// The canonical id of a trigger's firing, shared by `Planner` and `Scheduler`.
+    ///
+    /// Uses millisecond precision, so sub-second firings of the same trigger
+    /// get distinct ids.
+    pub fn canonical_id(trigger_id: &str, fire_at: DateTime<Utc>) -> String {
+        format!("{}-{}", trigger_id, fire_at.timestamp_millis())
+    }
 }
 
 impl Ord for Task {
     /// Compares two tasks based on their `run_at` execution time, 
-    /// ordering them to prioritize earlier tasks.
+    /// ordering them to prioritize earlier tasks. Tasks due at the same
+    /// time are ordered by id.
     ///
     /// This implementation reverses the ordering to ensure that tasks 
     /// with earlier `run_at` times are placed at the top of collections 
@@ -47,15 +58,18 @@ impl Ord for Task {
     /// # Examples
     ///
     /// ```
-    /// # use your_module::Task;
+    /// # use schedule::task::Task;
     /// # use chrono::Utc;
     /// # use std::cmp::Ordering;
     /// let task1 = Task { id: String::from("1"), run_at: Utc::now(), workflow_id: String::from("wf1"), trigger_id: String::from("tg1") };
     /// let task2 = Task { id: String::from("2"), run_at: Utc::now() + chrono::Duration::seconds(10), workflow_id: String::from("wf2"), trigger_id: String::from("tg2") };
-    /// assert_eq!(task1.cmp(&task2), Ordering::Less);
+    /// assert_eq!(task1.cmp(&task2), Ordering::Greater);
     /// ```
     fn cmp(&self, other: &Self) -> Ordering {
-        other.run_at.cmp(&self.run_at)
+        other
+            .run_at
+            .cmp(&self.run_at)
+            .then_with(|| other.id.cmp(&self.id))
     }
 }
 
@@ -70,13 +84,12 @@ impl PartialOrd for Task {
 }
 
 impl PartialEq for Task {
-    /// Checks equality between two tasks based on their execution time.
+    /// Checks equality between two tasks based on their execution time and id.
     ///
-    /// This implementation only compares the `run_at` field, considering
-    /// two tasks equal if they have the same execution time, regardless
-    /// of other fields.
+    /// This is consistent with the `Ord` implementation: two tasks are equal
+    /// only if they are the same firing.
     fn eq(&self, other: &Self) -> bool {
-        self.run_at == other.run_at
+        self.run_at == other.run_at && self.id == other.id
     }
 }
 
@@ -113,8 +126,37 @@ fn test_task_ordering() {
         let now = Utc::now();
         let task1 = create_task("task1", now, "workflow1", "trigger1");
         let task2 = create_task("task2", now, "workflow2", "trigger2");
+        let task1_again = create_task("task1", now, "workflow1", "trigger1");
 
-        assert_eq!(task1, task2, "Tasks with the same run_at should be equal");
+        assert_ne!(task1, task2, "Different tasks at the same run_at should not be equal");
+        assert_eq!(task1, task1_again, "The same firing should be equal");
+    }
+
+    #[test]
+    fn test_same_run_at_ordered_by_id() {
+        let now = Utc::now();
+        let task_a = create_task("a", now, "workflow1", "trigger1");
+        let task_b = create_task("b", now, "workflow2", "trigger2");
+
+        // 同一时间的任务按 id 排序，堆顶是 id 较小的任务
+        assert_eq!(task_a.cmp(&task_b), Ordering::Greater);
+        assert_eq!(task_a.cmp(&task_a), Ordering::Equal);
+
+        let mut heap = BinaryHeap::from(vec![task_b, task_a]);
+        assert_eq!(heap.pop().unwrap().id, "a");
+    }
+
+    #[test]
+    fn test_canonical_id_uses_milliseconds() {
+        let now = Utc::now();
+        assert_ne!(
+            Task::canonical_id("trigger1", now),
+            Task::canonical_id("trigger1", now + Duration::milliseconds(500))
+        );
+        assert_eq!(
+            Task::canonical_id("trigger1", now),
+            format!("trigger1-{}", now.timestamp_millis())
+        );
     }
 
     #[test]
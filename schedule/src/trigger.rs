@@ -1,26 +1,252 @@
 use std::str::FromStr;
+use std::sync::Arc;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
 
+use crate::calendar::Calendar;
+
+/// How far local wall-clock times can drift from UTC-ordered times around a
+/// DST transition. Real-world transitions shift by at most a few hours.
+const DST_MARGIN_HOURS: i64 = 3;
+
+/// How far `preview` looks ahead before giving up on finding more fire times.
+const PREVIEW_HORIZON_DAYS: i64 = 366 * 10;
+
+/// 64-bit FNV-1a hash, stable across runs and platforms unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// What to do with the firings a trigger missed while the scheduler was down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MisfirePolicy {
+    /// Fire once right away for all missed firings, then continue normally.
+    #[default]
+    FireOnceNow,
+
+    /// Fire every missed firing, oldest first.
+    FireAll,
+
+    /// Skip the missed firings and wait for the next one.
+    Skip,
+}
+
+/// What to do when a trigger fires while its previous run is still going.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Start the new run alongside the running ones.
+    #[default]
+    Allow,
+
+    /// Drop the new firing.
+    Skip,
+
+    /// Start the new run once the running one completes.
+    Queue,
+
+    /// Cancel the running ones and start the new run.
+    CancelPrevious,
+}
+
+/// When a trigger fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerKind {
+    /// Fires according to a cron expression, evaluated in the trigger's time zone.
+    Cron(String),
+
+    /// Fires every `every`, starting at `anchor`.
+    Interval {
+        every: Duration,
+        anchor: DateTime<Utc>,
+    },
+
+    /// Fires once at the given time.
+    Once(DateTime<Utc>),
+}
+
 pub struct Trigger {
     pub id: String,
-    pub cron_expr: String,
+    pub kind: TriggerKind,
     pub workflow_id: String,
+    pub misfire_policy: MisfirePolicy,
+    /// What to do when the trigger fires while its previous run is still going.
+    pub overlap_policy: OverlapPolicy,
+    /// Time zone the cron expression is evaluated in. Only affects cron triggers.
+    pub timezone: Tz,
+    /// The trigger never fires before this time.
+    pub start_at: Option<DateTime<Utc>>,
+    /// The trigger never fires after this time.
+    pub end_at: Option<DateTime<Utc>>,
+    /// The trigger fires at most this many times.
+    pub max_fires: Option<u32>,
+    /// Calendars whose excluded instants the trigger skips.
+    pub calendars: Vec<Arc<Calendar>>,
+    /// Upper bound of the delay added to every fire time, see [`Trigger::jitter`].
+    pub max_jitter: Option<Duration>,
 }
 
 impl Trigger {
-    /// Creates a new `Trigger` instance.
+    /// Creates a new cron `Trigger` instance.
     pub fn new(id: &str, cron_expr: &str, workflow_id: &str) -> Self {
+        Self::with_kind(id, TriggerKind::Cron(cron_expr.to_string()), workflow_id)
+    }
+
+    /// Creates a trigger that fires every `every`, starting at `anchor`.
+    pub fn interval(id: &str, every: Duration, anchor: DateTime<Utc>, workflow_id: &str) -> Self {
+        Self::with_kind(id, TriggerKind::Interval { every, anchor }, workflow_id)
+    }
+
+    /// Creates a trigger that fires once at `at`.
+    pub fn once(id: &str, at: DateTime<Utc>, workflow_id: &str) -> Self {
+        Self::with_kind(id, TriggerKind::Once(at), workflow_id)
+    }
+
+    /// Creates a trigger of the given kind.
+    pub fn with_kind(id: &str, kind: TriggerKind, workflow_id: &str) -> Self {
         Self {
             id: id.to_string(),
-            cron_expr: cron_expr.to_string(),
+            kind,
             workflow_id: workflow_id.to_string(),
+            misfire_policy: MisfirePolicy::default(),
+            overlap_policy: OverlapPolicy::default(),
+            timezone: Tz::UTC,
+            start_at: None,
+            end_at: None,
+            max_fires: None,
+            calendars: Vec::new(),
+            max_jitter: None,
+        }
+    }
+
+    /// Sets the policy for firings missed while the scheduler was down.
+    pub fn with_misfire_policy(mut self, policy: MisfirePolicy) -> Self {
+        self.misfire_policy = policy;
+        self
+    }
+
+    /// Sets the policy for firings that overlap a run still in progress.
+    ///
+    /// Policies other than `Allow` rely on the executor reporting completed
+    /// runs with a [`RunCompleted`](crate::scheduler::RunCompleted) message.
+    pub fn with_overlap_policy(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap_policy = policy;
+        self
+    }
+
+    /// Sets the IANA time zone the cron expression is evaluated in, e.g. `Asia/Shanghai`.
+    ///
+    /// Local times skipped by a DST transition fire after the gap, shifted by its
+    /// length. Local times repeated by a DST transition fire once, at the first
+    /// occurrence.
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the earliest time the trigger may fire, inclusive.
+    pub fn with_start_at(mut self, start_at: DateTime<Utc>) -> Self {
+        self.start_at = Some(start_at);
+        self
+    }
+
+    /// Sets the latest time the trigger may fire, inclusive.
+    pub fn with_end_at(mut self, end_at: DateTime<Utc>) -> Self {
+        self.end_at = Some(end_at);
+        self
+    }
+
+    /// Limits the total number of firings.
+    pub fn with_max_fires(mut self, max_fires: u32) -> Self {
+        self.max_fires = Some(max_fires);
+        self
+    }
+
+    /// Attaches a calendar; instants it excludes are skipped.
+    pub fn with_calendar(mut self, calendar: Arc<Calendar>) -> Self {
+        self.calendars.push(calendar);
+        self
+    }
+
+    /// Returns whether any attached calendar excludes `instant`.
+    pub fn is_excluded(&self, instant: DateTime<Utc>) -> bool {
+        self.calendars.iter().any(|calendar| calendar.is_excluded(instant))
+    }
+
+    /// Returns the next `n` effective fire times, including jitter, at or after `from`.
+    ///
+    /// Bounds, calendar exclusions, `max_fires` (counted from `fired` firings)
+    /// and jitter are applied the same way the planner applies them. Misfire and
+    /// overlap policies depend on the scheduler's state and are not, so a
+    /// previewed time may still be skipped or coalesced when it comes due.
+    pub fn preview(&self, from: DateTime<Utc>, n: usize, fired: u32) -> Vec<DateTime<Utc>> {
+        let n = n.min(self.remaining_fires(fired).unwrap_or(usize::MAX));
+        let jitter = self.jitter();
+        let horizon = from + Duration::days(PREVIEW_HORIZON_DAYS);
+        let mut times = Vec::new();
+
+        // 窗口逐步加倍，稀疏的触发器也能很快找到足够的时间；
+        // 从 from - jitter 开始查找，加上偏移后不早于 from 的时间都要包含
+        let mut start = from - jitter;
+        let mut span = Duration::hours(1);
+        while times.len() < n && start < horizon {
+            if self.end_at.is_some_and(|end_at| start > end_at) {
+                break;
+            }
+            let end = (start + span).min(horizon);
+            times.extend(self.next_run_times(start, end));
+            start = end;
+            span = (span * 2).min(Duration::days(366));
         }
+
+        times.truncate(n);
+        times.into_iter().map(|time| time + jitter).collect()
+    }
+
+    /// Delays every fire time by a fixed offset in `[0, max_jitter)`.
+    ///
+    /// Spreads triggers sharing the same schedule so they do not all fire at once.
+    pub fn with_jitter(mut self, max_jitter: Duration) -> Self {
+        self.max_jitter = Some(max_jitter);
+        self
+    }
+
+    /// The delay added to every fire time of this trigger.
+    ///
+    /// Derived from a hash of the trigger id, so it is the same on every
+    /// planning round and after a restart.
+    pub fn jitter(&self) -> Duration {
+        match self.max_jitter {
+            Some(max_jitter) if max_jitter.num_milliseconds() > 0 => {
+                let max = max_jitter.num_milliseconds() as u64;
+                Duration::milliseconds((fnv1a(self.id.as_bytes()) % max) as i64)
+            }
+            _ => Duration::zero(),
+        }
+    }
+
+    /// Returns how many more times the trigger may fire after firing `fired` times,
+    /// or `None` if it is unlimited.
+    pub fn remaining_fires(&self, fired: u32) -> Option<usize> {
+        self.max_fires.map(|max| max.saturating_sub(fired) as usize)
     }
 
     /// Generates the next run times within the specified time window.
     ///
+    /// The window is narrowed to the trigger's `start_at` / `end_at` bounds,
+    /// and instants excluded by an attached calendar are skipped. The times are
+    /// returned before jitter, but `end_at` and the calendars are checked against
+    /// the jittered time the task actually runs at.
+    /// Cron expressions are evaluated in the trigger's time zone; the run times
+    /// are returned in UTC, sorted and without duplicates. `max_fires` is not
+    /// applied here since it depends on how often the trigger already fired.
+    ///
     /// # Arguments
     ///
     /// * `start` - The start of the time window.
@@ -30,24 +256,109 @@ impl Trigger {
     ///
     /// * `Vec<DateTime<Utc>>` - A vector of `DateTime<Utc>` representing the next run times within the window.
     pub fn next_run_times(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let start = self.start_at.map_or(start, |start_at| start.max(start_at));
+        if start >= end {
+            return Vec::new();
+        }
+
+        let mut times = match &self.kind {
+            TriggerKind::Cron(cron_expr) => self.cron_run_times(cron_expr, start, end),
+            TriggerKind::Interval { every, anchor } => {
+                Self::interval_run_times(*every, *anchor, start, end)
+            }
+            TriggerKind::Once(at) if *at >= start && *at < end => vec![*at],
+            TriggerKind::Once(_) => Vec::new(),
+        };
+
+        let jitter = self.jitter();
+        if let Some(end_at) = self.end_at {
+            times.retain(|time| *time + jitter <= end_at);
+        }
+        times.retain(|time| !self.is_excluded(*time + jitter));
+        times
+    }
+
+    fn cron_run_times(&self, cron_expr: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DateTime<Utc>> {
         let mut times = Vec::new();
 
-        let schedule = match Schedule::from_str(&self.cron_expr) {
+        let schedule = match Schedule::from_str(cron_expr) {
             Ok(s) => s,
             Err(_) => {
-                eprintln!("Invalid cron expression: {}", self.cron_expr);
+                eprintln!("Invalid cron expression: {}", cron_expr);
                 return times;
             }
         };
+        // 按本地时间计算 cron，再换算回 UTC；夏令时附近本地时间与 UTC 的先后不一致，
+        // 所以前后各多查找一段时间，换算后再按窗口过滤；UTC 没有夏令时，不需要
+        let margin = if self.timezone == Tz::UTC {
+            Duration::zero()
+        } else {
+            Duration::hours(DST_MARGIN_HOURS)
+        };
+        let from = self.local_time(start) - margin - Duration::seconds(1);
+        let until = self.local_time(end) + margin;
 
-        for datetime in schedule.upcoming(Utc).take_while(|&dt| dt < end) {
+        for local in schedule
+            .after(&Utc.from_utc_datetime(&from))
+            .map(|dt| dt.naive_utc())
+            .take_while(|&local| local < until)
+        {
+            let datetime = self.resolve(local);
             if datetime >= start && datetime < end {
                 times.push(datetime);
             }
         }
 
+        times.sort();
+        times.dedup();
         times
     }
+
+    /// Run times `anchor + k * every` within `[start, end)`.
+    fn interval_run_times(every: Duration, anchor: DateTime<Utc>, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        if every <= Duration::zero() {
+            eprintln!("Invalid interval: {}", every);
+            return Vec::new();
+        }
+
+        // 窗口起点之后的第一次触发
+        let mut time = anchor;
+        if start > anchor {
+            let step = every.num_nanoseconds().unwrap_or(i64::MAX) as i128;
+            let elapsed = (start - anchor).num_nanoseconds().unwrap_or(i64::MAX) as i128;
+            let skipped = (elapsed + step - 1) / step;
+            time = anchor + Duration::nanoseconds((skipped * step).min(i64::MAX as i128) as i64);
+        }
+
+        let mut times = Vec::new();
+        while time < end {
+            if time >= start {
+                times.push(time);
+            }
+            time += every;
+        }
+        times
+    }
+
+    /// Wall-clock time in the trigger's time zone.
+    fn local_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        instant.with_timezone(&self.timezone).naive_local()
+    }
+
+    /// Converts a wall-clock time in the trigger's time zone to UTC.
+    fn resolve(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self.timezone.from_local_datetime(&local) {
+            LocalResult::Single(datetime) => datetime.with_timezone(&Utc),
+            // 重复的本地时间只在第一次出现时触发
+            LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+            // 被跳过的本地时间按跳变前的偏移换算，相当于顺延跳过的时长
+            LocalResult::None => {
+                let before = Utc.from_utc_datetime(&(local - Duration::days(1)));
+                let offset = self.timezone.offset_from_utc_datetime(&before.naive_utc()).fix();
+                Utc.from_utc_datetime(&(local - Duration::seconds(offset.local_minus_utc() as i64)))
+            }
+        }
+    }
 }
 
 
@@ -64,27 +375,29 @@ mod tests {
     fn test_next_run_times_with_valid_cron() {
         // 设置触发器和时间窗口
         let trigger = create_trigger("trigger1", "*/5 * * * * *", "workflow1");
-        let start = Utc::now();
-        let end = start + Duration::seconds(20); // 未来20秒的时间窗口
+        let start = utc("2024-01-01T00:00:00Z");
+        let end = start + Duration::seconds(20); // 20秒的时间窗口
 
         // 获取运行时间
         let times = trigger.next_run_times(start, end);
 
-        // 验证生成的时间数量和边界
-        assert!(!times.is_empty(), "Expected run times to be generated.");
-        assert!(times.len() >= 1, "Expected at least one run time within the time window.");
-
-        for time in &times {
-            assert!(*time >= start && *time < end,
-                    "Run time is not within the specified time window.");
-        }
+        // 验证生成的时间
+        assert_eq!(
+            times,
+            vec![
+                utc("2024-01-01T00:00:00Z"),
+                utc("2024-01-01T00:00:05Z"),
+                utc("2024-01-01T00:00:10Z"),
+                utc("2024-01-01T00:00:15Z"),
+            ]
+        );
     }
 
     #[test]
     fn test_next_run_times_with_invalid_cron() {
         // 设置无效的cron表达式触发器
         let trigger = create_trigger("invalid_trigger", "invalid cron expr", "workflow1");
-        let start = Utc::now();
+        let start = utc("2024-01-01T00:00:00Z");
         let end = start + Duration::seconds(20);
 
         // 获取运行时间
@@ -98,20 +411,242 @@ mod tests {
     fn test_next_run_times_with_edge_of_time_window() {
         // 设置触发器，每5秒执行一次
         let trigger = create_trigger("trigger2", "*/5 * * * * *", "workflow2");
-        let start = Utc::now();
-        let end = start + Duration::seconds(10); // 未来10秒的时间窗口
+        let start = utc("2024-01-01T00:00:02Z");
+        let end = start + Duration::seconds(10); // 10秒的时间窗口
 
-        // 获取运行时间
+        // 窗口内的触发时间
         let times = trigger.next_run_times(start, end);
+        assert_eq!(times, vec![utc("2024-01-01T00:00:05Z"), utc("2024-01-01T00:00:10Z")]);
 
-        // 验证生成的时间是否在指定的窗口范围内
-        assert!(!times.is_empty(), "Expected run times to be generated.");
-        for time in &times {
-            assert!(*time >= start && *time < end,
-                    "Run time is not within the specified time window.");
-        }
+        // 窗口包含起点，不包含终点
+        let times = trigger.next_run_times(utc("2024-01-01T00:00:05Z"), utc("2024-01-01T00:00:15Z"));
+        assert_eq!(times, vec![utc("2024-01-01T00:00:05Z"), utc("2024-01-01T00:00:10Z")]);
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_run_times_in_time_zone() {
+        // 上海时间工作日 09:00，即 UTC 01:00
+        let trigger = create_trigger("trigger3", "0 0 9 * * Mon-Fri", "workflow3")
+            .with_timezone(chrono_tz::Asia::Shanghai);
+        let times = trigger.next_run_times(utc("2024-01-01T00:00:00Z"), utc("2024-01-08T00:00:00Z"));
+
+        assert_eq!(times.len(), 5);
+        assert_eq!(times[0], utc("2024-01-01T01:00:00Z"));
+        assert_eq!(times[4], utc("2024-01-05T01:00:00Z"));
+    }
+
+    #[test]
+    fn test_next_run_times_across_skipped_hour() {
+        // 纽约 2024-03-10 02:30 不存在，顺延到夏令时的 03:30
+        let trigger = create_trigger("trigger4", "0 30 2 * * *", "workflow4")
+            .with_timezone(chrono_tz::America::New_York);
+        let times = trigger.next_run_times(utc("2024-03-09T00:00:00Z"), utc("2024-03-12T00:00:00Z"));
+
+        assert_eq!(
+            times,
+            vec![
+                utc("2024-03-09T07:30:00Z"),
+                utc("2024-03-10T07:30:00Z"),
+                utc("2024-03-11T06:30:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_run_times_across_repeated_hour() {
+        // 纽约 2024-11-03 01:30 出现两次，只在第一次触发
+        let trigger = create_trigger("trigger5", "0 30 1 * * *", "workflow5")
+            .with_timezone(chrono_tz::America::New_York);
+        let times = trigger.next_run_times(utc("2024-11-03T00:00:00Z"), utc("2024-11-04T00:00:00Z"));
+
+        assert_eq!(times, vec![utc("2024-11-03T05:30:00Z")]);
+    }
+
+    #[test]
+    fn test_interval_run_times() {
+        // 每 90 分钟一次，从锚点开始对齐
+        let anchor = utc("2024-01-01T00:00:00Z");
+        let trigger = Trigger::interval("trigger6", Duration::minutes(90), anchor, "workflow6");
+        let times = trigger.next_run_times(utc("2024-01-01T02:00:00Z"), utc("2024-01-01T06:00:00Z"));
+
+        assert_eq!(
+            times,
+            vec![
+                utc("2024-01-01T03:00:00Z"),
+                utc("2024-01-01T04:30:00Z"),
+            ]
+        );
+
+        // 窗口起点恰好在触发时间上时包含该时间
+        let times = trigger.next_run_times(utc("2024-01-01T03:00:00Z"), utc("2024-01-01T04:00:00Z"));
+        assert_eq!(times, vec![utc("2024-01-01T03:00:00Z")]);
+    }
+
+    #[test]
+    fn test_once_run_times() {
+        let at = utc("2024-01-01T12:00:00Z");
+        let trigger = Trigger::once("trigger7", at, "workflow7");
+
+        assert_eq!(
+            trigger.next_run_times(utc("2024-01-01T00:00:00Z"), utc("2024-01-02T00:00:00Z")),
+            vec![at]
+        );
+        assert!(trigger
+            .next_run_times(utc("2024-01-01T12:00:01Z"), utc("2024-01-02T00:00:00Z"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_bounded_cron_run_times() {
+        // 每小时一次，只在 start_at 和 end_at 之间触发
+        let trigger = create_trigger("trigger8", "0 0 * * * *", "workflow8")
+            .with_start_at(utc("2024-01-01T03:00:00Z"))
+            .with_end_at(utc("2024-01-01T05:00:00Z"));
+        let times = trigger.next_run_times(utc("2024-01-01T00:00:00Z"), utc("2024-01-02T00:00:00Z"));
+
+        assert_eq!(
+            times,
+            vec![
+                utc("2024-01-01T03:00:00Z"),
+                utc("2024-01-01T04:00:00Z"),
+                utc("2024-01-01T05:00:00Z"),
+            ]
+        );
+        assert_eq!(trigger.remaining_fires(2), None);
+        assert_eq!(trigger.with_max_fires(3).remaining_fires(2), Some(1));
+    }
+
+    #[test]
+    fn test_calendar_exclusions() {
+        // 每天 09:00，跳过元旦和 1 月 3 日的维护窗口
+        let calendar = Arc::new(
+            Calendar::new("ops")
+                .exclude_date(chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap())
+                .exclude_range(utc("2024-01-03T08:00:00Z"), utc("2024-01-03T10:00:00Z")),
+        );
+        let trigger = create_trigger("trigger9", "0 0 9 * * *", "workflow9").with_calendar(calendar);
+        let times = trigger.next_run_times(utc("2024-01-01T00:00:00Z"), utc("2024-01-05T00:00:00Z"));
+
+        assert_eq!(times, vec![utc("2024-01-02T09:00:00Z"), utc("2024-01-04T09:00:00Z")]);
+    }
+
+    #[test]
+    fn test_preview_next_effective_fire_times() {
+        let calendar = Arc::new(
+            Calendar::new("holidays").exclude_date(chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()),
+        );
+        let trigger = create_trigger("trigger10", "0 0 9 * * *", "workflow10").with_calendar(calendar);
+
+        assert_eq!(
+            trigger.preview(utc("2024-01-01T00:00:00Z"), 3, 0),
+            vec![
+                utc("2024-01-01T09:00:00Z"),
+                utc("2024-01-03T09:00:00Z"),
+                utc("2024-01-04T09:00:00Z"),
+            ]
+        );
+
+        // 受 max_fires 和 end_at 限制
+        let trigger = trigger.with_max_fires(4);
+        assert_eq!(trigger.preview(utc("2024-01-01T00:00:00Z"), 3, 2).len(), 2);
+        let trigger = trigger.with_end_at(utc("2024-01-01T12:00:00Z"));
+        assert_eq!(trigger.preview(utc("2024-01-01T00:00:00Z"), 3, 0).len(), 1);
+
+        // 永远不会触发的表达式在预览范围内找不到时间
+        let trigger = create_trigger("trigger11", "0 0 0 30 2 *", "workflow11");
+        assert!(trigger.preview(utc("2024-01-01T00:00:00Z"), 1, 0).is_empty());
+    }
+
+    #[test]
+    fn test_jitter_is_deterministic_and_bounded() {
+        let max = Duration::seconds(30);
+        let jitters: Vec<Duration> = (0..100)
+            .map(|i| create_trigger(&format!("trigger{}", i), "0 * * * * *", "workflow").with_jitter(max).jitter())
+            .collect();
+
+        assert!(jitters.iter().all(|jitter| *jitter >= Duration::zero() && *jitter < max));
+        // 同一个 id 的偏移总是相同，不同 id 的偏移分散开
+        assert_eq!(
+            jitters[7],
+            create_trigger("trigger7", "0 * * * * *", "other").with_jitter(max).jitter()
+        );
+        let distinct: std::collections::HashSet<_> = jitters.iter().collect();
+        assert!(distinct.len() > 90);
+        assert_eq!(create_trigger("trigger1", "0 * * * * *", "workflow").jitter(), Duration::zero());
+    }
+
+    #[test]
+    fn test_preview_includes_jitter() {
+        let trigger = create_trigger("trigger12", "0 0 * * * *", "workflow12").with_jitter(Duration::minutes(10));
+        let jitter = trigger.jitter();
+
+        // 预览的时间与 Planner 生成的任务时间一致
+        let planned: Vec<DateTime<Utc>> = crate::planner::Planner::plan_trigger(&trigger, utc("2024-01-01T00:00:00Z"), utc("2024-01-01T02:00:00Z"), 0)
+            .into_iter()
+            .map(|task| task.run_at)
+            .collect();
+        assert_eq!(
+            trigger.preview(utc("2024-01-01T00:00:00Z"), 2, 0),
+            vec![utc("2024-01-01T00:00:00Z") + jitter, utc("2024-01-01T01:00:00Z") + jitter]
+        );
+        assert_eq!(trigger.preview(utc("2024-01-01T00:00:00Z"), 2, 0), planned);
+
+        // 偏移后仍不早于 from 的触发时间也在预览中
+        assert_eq!(
+            trigger.preview(utc("2024-01-01T00:00:00Z") + jitter, 1, 0),
+            vec![utc("2024-01-01T00:00:00Z") + jitter]
+        );
+    }
+
+    #[test]
+    fn test_jitter_respects_end_at() {
+        let trigger = create_trigger("trigger12", "0 0 * * * *", "workflow12").with_jitter(Duration::minutes(10));
+        let jitter = trigger.jitter();
+        assert!(jitter > Duration::zero());
+        let fire_time = utc("2024-01-01T01:00:00Z");
+
+        // 偏移后的时间正好在 end_at 上时仍然触发
+        let trigger = trigger.with_end_at(fire_time + jitter);
+        assert_eq!(
+            trigger.next_run_times(utc("2024-01-01T00:30:00Z"), utc("2024-01-01T02:00:00Z")),
+            vec![fire_time]
+        );
+        assert_eq!(trigger.preview(utc("2024-01-01T00:30:00Z"), 2, 0), vec![fire_time + jitter]);
+
+        // 触发时间在 end_at 之前，但偏移后超过 end_at，不再触发
+        let trigger = trigger.with_end_at(fire_time + jitter - Duration::milliseconds(1));
+        assert!(trigger.next_run_times(utc("2024-01-01T00:30:00Z"), utc("2024-01-01T02:00:00Z")).is_empty());
+        assert!(trigger.preview(utc("2024-01-01T00:30:00Z"), 2, 0).is_empty());
+    }
+
+    #[test]
+    fn test_jitter_respects_calendar() {
+        let trigger = || create_trigger("trigger12", "0 0 * * * *", "workflow12").with_jitter(Duration::minutes(10));
+        let jitter = trigger().jitter();
+        assert!(jitter > Duration::zero());
+        let fire_time = utc("2024-01-01T01:00:00Z");
+
+        // 排除区间只覆盖偏移前的时间，任务仍在区间之后运行
+        let calendar = Arc::new(Calendar::new("before").exclude_range(fire_time, fire_time + jitter));
+        let before = trigger().with_calendar(calendar);
+        assert_eq!(
+            before.next_run_times(utc("2024-01-01T00:30:00Z"), utc("2024-01-01T01:30:00Z")),
+            vec![fire_time]
+        );
 
-        // 验证是否包含正确的时间数量
-        assert!(times.len() <= 2, "Expected at most two run times within a 10-second window.");
+        // 排除区间覆盖偏移后的时间，跳过这次触发
+        let calendar = Arc::new(
+            Calendar::new("after").exclude_range(fire_time + jitter, fire_time + jitter + Duration::milliseconds(1)),
+        );
+        let after = trigger().with_calendar(calendar);
+        assert!(after.next_run_times(utc("2024-01-01T00:30:00Z"), utc("2024-01-01T01:30:00Z")).is_empty());
+        assert!(after
+            .preview(utc("2024-01-01T00:30:00Z"), 2, 0)
+            .iter()
+            .all(|time| *time != fire_time + jitter));
     }
 }
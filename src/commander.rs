@@ -1,13 +1,84 @@
+use std::{collections::HashMap, future::Future, marker::PhantomData, time::Instant};
+
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use thiserror::Error;
+
+/// 命令执行失败的原因
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum CommandError {
+    #[error("命令 '{0}' 未注册")]
+    NotFound(String),
+
+    #[error("命令 '{0}' 重复注册")]
+    Duplicate(String),
+
+    #[error("参数提取失败: {0}")]
+    Extract(String),
+
+    #[error("未授权: {0}")]
+    Unauthorized(String),
 
+    #[error("命令执行失败: {0}")]
+    Failed(String),
+}
+
+/// 命令执行的上下文，处理器的参数都从这里提取
+#[derive(Debug, Clone, Default)]
 pub struct Context {
-    user_id: Option<String>,
-    request_id: Option<String>,
+    pub user_id: Option<String>,
+    pub request_id: Option<String>,
+    // 工作流运行 id
+    pub run_id: Option<String>,
+    // 命令的载荷
+    pub payload: Value,
+    // 节点参数
+    pub params: Value,
+    // 上游节点的输出，节点 id 为 key
+    pub upstream: HashMap<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// 添加一个上游节点的输出
+    pub fn with_upstream(mut self, node_id: impl Into<String>, output: Value) -> Self {
+        self.upstream.insert(node_id.into(), output);
+        self
+    }
 }
 
 #[async_trait]
 pub trait FromContext: Sized {
-    async fn from_context(ctx: &Context) -> Result<Self, &'static str>;
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError>;
 }
 
 pub struct UserInfo {
@@ -16,8 +87,11 @@ pub struct UserInfo {
 
 #[async_trait]
 impl FromContext for UserInfo {
-    async fn from_context(ctx: &Context) -> Result<Self, &'static str> {
-        let user_id = ctx.user_id.clone().ok_or("user_id not found")?;
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        let user_id = ctx
+            .user_id
+            .clone()
+            .ok_or_else(|| CommandError::Extract("user_id not found".to_string()))?;
         Ok(UserInfo { user_id })
     }
 }
@@ -28,67 +102,500 @@ pub struct RequestInfo {
 
 #[async_trait]
 impl FromContext for RequestInfo {
-    async fn from_context(ctx: &Context) -> Result<Self, &'static str> {
-        let request_id = ctx.request_id.clone().ok_or("request_id not found")?;
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        let request_id = ctx
+            .request_id
+            .clone()
+            .ok_or_else(|| CommandError::Extract("request_id not found".to_string()))?;
         Ok(RequestInfo { request_id })
     }
 }
 
+/// 命令载荷，反序列化为 T
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromContext for Json<T> {
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        serde_json::from_value(ctx.payload.clone())
+            .map(Json)
+            .map_err(|err| CommandError::Extract(format!("payload: {}", err)))
+    }
+}
+
+/// 工作流运行 id
+pub struct RunId(pub String);
+
+#[async_trait]
+impl FromContext for RunId {
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        ctx.run_id
+            .clone()
+            .map(RunId)
+            .ok_or_else(|| CommandError::Extract("run_id not found".to_string()))
+    }
+}
+
+/// 节点参数，反序列化为 T
+pub struct Params<T>(pub T);
+
 #[async_trait]
-pub trait CommandHandler {
-    async fn call(&self, ctx: &Context) -> Result<(), &'static str>;
+impl<T: DeserializeOwned> FromContext for Params<T> {
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        serde_json::from_value(ctx.params.clone())
+            .map(Params)
+            .map_err(|err| CommandError::Extract(format!("params: {}", err)))
+    }
 }
 
-// 定义命令者结构体
-struct Commander {
-    commands: Vec<Box<dyn CommandHandler + Send + Sync>>,
+/// 上游节点的输出，节点 id 为 key
+pub struct Upstream(pub HashMap<String, Value>);
+
+#[async_trait]
+impl FromContext for Upstream {
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        Ok(Upstream(ctx.upstream.clone()))
+    }
+}
+
+/// 可选的参数，提取失败时为 None
+#[async_trait]
+impl<T: FromContext> FromContext for Option<T> {
+    async fn from_context(ctx: &Context) -> Result<Self, CommandError> {
+        Ok(T::from_context(ctx).await.ok())
+    }
+}
+
+/// 命令处理器
+///
+/// 参数都实现了 `FromContext` 的异步函数可以直接作为处理器，`Args` 是参数类型组成的元组，
+/// 用于区分不同参数个数的函数。最多支持 8 个参数。
+#[async_trait]
+pub trait CommandHandler<Args>: Send + Sync + 'static {
+    async fn handle(&self, ctx: &Context) -> Result<(), CommandError>;
+}
+
+macro_rules! impl_command_handler {
+    ($($ty:ident),*) => {
+        #[async_trait]
+        #[allow(non_snake_case)]
+        impl<F, Fut, $($ty,)*> CommandHandler<($($ty,)*)> for F
+        where
+            F: Fn($($ty),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<(), CommandError>> + Send,
+            $($ty: FromContext + Send + 'static,)*
+        {
+            async fn handle(&self, _ctx: &Context) -> Result<(), CommandError> {
+                // 按参数顺序逐个提取，任意一个失败都不会执行处理器
+                $(let $ty = $ty::from_context(_ctx).await?;)*
+                (self)($($ty),*).await
+            }
+        }
+    };
+}
+
+impl_command_handler!();
+impl_command_handler!(T1);
+impl_command_handler!(T1, T2);
+impl_command_handler!(T1, T2, T3);
+impl_command_handler!(T1, T2, T3, T4);
+impl_command_handler!(T1, T2, T3, T4, T5);
+impl_command_handler!(T1, T2, T3, T4, T5, T6);
+impl_command_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_command_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+// 擦除了参数类型的处理器，便于统一存放
+#[async_trait]
+trait Command: Send + Sync {
+    async fn call(&self, ctx: &Context) -> Result<(), CommandError>;
+}
+
+struct HandlerCommand<F, Args> {
+    handler: F,
+    _args: PhantomData<fn() -> Args>,
+}
+
+#[async_trait]
+impl<F, Args> Command for HandlerCommand<F, Args>
+where
+    F: CommandHandler<Args>,
+    Args: 'static,
+{
+    async fn call(&self, ctx: &Context) -> Result<(), CommandError> {
+        self.handler.handle(ctx).await
+    }
+}
+
+/// 包裹每一次命令调用的中间件
+///
+/// 中间件可以修改上下文、提前返回错误，或者在调用 `next.run` 前后做额外的处理。
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(&self, ctx: &mut Context, next: Next<'_>) -> Result<(), CommandError>;
+}
+
+/// 调用链中剩余的部分：后面的中间件和命令本身
+pub struct Next<'a> {
+    command: &'a str,
+    middleware: &'a [Box<dyn Middleware>],
+    handler: &'a dyn Command,
+}
+
+impl Next<'_> {
+    /// 当前执行的命令名
+    pub fn command(&self) -> &str {
+        self.command
+    }
+
+    /// 继续执行调用链
+    pub async fn run(self, ctx: &mut Context) -> Result<(), CommandError> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                let next = Next {
+                    middleware: rest,
+                    ..self
+                };
+                first.handle(ctx, next).await
+            }
+            None => self.handler.call(ctx).await,
+        }
+    }
+}
+
+/// 命令分发器，按名称注册处理器并按名称执行
+///
+/// 中间件按添加的顺序由外向内包裹每一次调用。
+#[derive(Default)]
+pub struct Commander {
+    commands: HashMap<String, Box<dyn Command>>,
+    middleware: Vec<Box<dyn Middleware>>,
 }
 
 impl Commander {
-    fn new() -> Self {
-        Commander { commands: vec![] }
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn add_command<F>(&mut self, func: F)
+    /// 以名称注册一个处理器，名称已存在时返回错误
+    pub fn register<F, Args>(
+        &mut self,
+        name: impl Into<String>,
+        handler: F,
+    ) -> Result<(), CommandError>
     where
-        F: CommandHandler + Send + Sync + 'static,
+        F: CommandHandler<Args>,
+        Args: 'static,
     {
-        self.commands.push(Box::new(func));
+        let name = name.into();
+        if self.commands.contains_key(&name) {
+            return Err(CommandError::Duplicate(name));
+        }
+        self.commands.insert(
+            name,
+            Box::new(HandlerCommand {
+                handler,
+                _args: PhantomData,
+            }),
+        );
+        Ok(())
+    }
+
+    /// 添加一个中间件
+    pub fn with_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    /// 是否注册了该命令
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    /// 已注册的命令名，按字典序排列
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.commands.keys().cloned().collect();
+        names.sort();
+        names
     }
 
-    async fn execute(&self, ctx: &Context) -> Result<(), &'static str> {
-        if let Some(command) = self.commands.first() {
-            command.call(ctx).await
-        } else {
-            Err("Command not found")
+    /// 执行指定名称的命令
+    pub async fn execute(&self, name: &str, mut ctx: Context) -> Result<(), CommandError> {
+        let handler = self
+            .commands
+            .get(name)
+            .ok_or_else(|| CommandError::NotFound(name.to_string()))?;
+        let next = Next {
+            command: name,
+            middleware: &self.middleware,
+            handler: handler.as_ref(),
+        };
+        next.run(&mut ctx).await
+    }
+}
+
+/// 要求上下文中带有用户，否则返回 `CommandError::Unauthorized`
+pub struct RequireUser;
+
+#[async_trait]
+impl Middleware for RequireUser {
+    async fn handle(&self, ctx: &mut Context, next: Next<'_>) -> Result<(), CommandError> {
+        if UserInfo::from_context(ctx).await.is_err() {
+            return Err(CommandError::Unauthorized(format!(
+                "命令 '{}' 需要用户身份",
+                next.command()
+            )));
         }
+        next.run(ctx).await
+    }
+}
+
+/// 保证每次调用都带有请求 id，缺少时生成一个，失败时连同请求 id 一起输出
+pub struct RequestId;
+
+#[async_trait]
+impl Middleware for RequestId {
+    async fn handle(&self, ctx: &mut Context, next: Next<'_>) -> Result<(), CommandError> {
+        if ctx.request_id.is_none() {
+            ctx.request_id = Some(nanoid::nanoid!());
+        }
+        let RequestInfo { request_id } = RequestInfo::from_context(ctx).await?;
+        let command = next.command().to_string();
+        let result = next.run(ctx).await;
+        if let Err(err) = &result {
+            println!("请求 {} 执行命令 '{}' 失败: {}", request_id, command, err);
+        }
+        result
+    }
+}
+
+/// 输出每次命令调用的耗时
+pub struct Timing;
+
+#[async_trait]
+impl Middleware for Timing {
+    async fn handle(&self, ctx: &mut Context, next: Next<'_>) -> Result<(), CommandError> {
+        let command = next.command().to_string();
+        let start = Instant::now();
+        let result = next.run(ctx).await;
+        println!("命令 '{}' 耗时 {:?}", command, start.elapsed());
+        result
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde::Deserialize;
+    use serde_json::json;
+
     use super::*;
 
-    pub async fn demo() -> Result<(), &'static str> {
+    pub async fn demo() -> Result<(), CommandError> {
         Ok(())
     }
 
+    async fn fail() -> Result<(), CommandError> {
+        Err(CommandError::Failed("boom".to_string()))
+    }
+
+    #[derive(Deserialize)]
+    struct Order {
+        id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct Threshold {
+        limit: u32,
+    }
+
+    // 记录处理器收到的参数
+    static SEEN: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    async fn approve(
+        Json(order): Json<Order>,
+        RunId(run_id): RunId,
+        Params(threshold): Params<Threshold>,
+        Upstream(upstream): Upstream,
+        user: UserInfo,
+    ) -> Result<(), CommandError> {
+        if order.id > threshold.limit {
+            return Err(CommandError::Failed("order over limit".to_string()));
+        }
+        SEEN.lock().unwrap().push(format!(
+            "{}:{}:{}:{}",
+            order.id, run_id, upstream["fetch"], user.user_id
+        ));
+        Ok(())
+    }
+
+    async fn whoami(user: Option<UserInfo>) -> Result<(), CommandError> {
+        match user {
+            Some(_) => Ok(()),
+            None => Err(CommandError::Failed("anonymous".to_string())),
+        }
+    }
+
+    async fn record_request(RequestInfo { request_id }: RequestInfo) -> Result<(), CommandError> {
+        SEEN.lock().unwrap().push(format!("request:{}", request_id));
+        Ok(())
+    }
+
+    // 记录调用顺序的中间件
+    struct Trace(&'static str, Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Middleware for Trace {
+        async fn handle(&self, ctx: &mut Context, next: Next<'_>) -> Result<(), CommandError> {
+            self.1
+                .lock()
+                .unwrap()
+                .push(format!("{} > {}", self.0, next.command()));
+            let result = next.run(ctx).await;
+            self.1.lock().unwrap().push(format!("{} <", self.0));
+            result
+        }
+    }
 
     #[tokio::test] // 使用 tokio 异步运行时测试
     async fn test_commander_demo() {
         let mut commander = Commander::new();
 
         // 注册命令
-        commander.add_command(demo);
+        commander.register("demo", demo).unwrap();
 
         // 创建一个上下文对象
-        let ctx = Context {
-            user_id: Some("user123".to_string()),
-            request_id: Some("req456".to_string()),
-        };
+        let ctx = Context::new()
+            .with_user_id("user123")
+            .with_request_id("req456");
 
         // 执行命令并传入上下文，检查是否成功
-        let result = commander.execute(&ctx).await;
+        let result = commander.execute("demo", ctx).await;
         assert!(result.is_ok(), "Commander failed to execute demo command");
     }
+
+    #[tokio::test]
+    async fn test_dispatch_by_name() {
+        let mut commander = Commander::new();
+        commander.register("demo", demo).unwrap();
+        commander.register("fail", fail).unwrap();
+        assert_eq!(commander.names(), vec!["demo", "fail"]);
+
+        assert_eq!(commander.execute("demo", Context::new()).await, Ok(()));
+        assert_eq!(
+            commander.execute("fail", Context::new()).await,
+            Err(CommandError::Failed("boom".to_string()))
+        );
+        assert_eq!(
+            commander.execute("missing", Context::new()).await,
+            Err(CommandError::NotFound("missing".to_string()))
+        );
+        assert_eq!(
+            commander.register("demo", fail),
+            Err(CommandError::Duplicate("demo".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extractors_resolve_from_context() {
+        let ctx = Context::new()
+            .with_user_id("user123")
+            .with_run_id("run-1")
+            .with_payload(json!({"id": 7}))
+            .with_params(json!({"limit": 10}))
+            .with_upstream("fetch", json!(3));
+
+        assert_eq!(approve.handle(&ctx).await, Ok(()));
+        assert!(SEEN
+            .lock()
+            .unwrap()
+            .contains(&"7:run-1:3:user123".to_string()));
+
+        // 处理器自身的错误原样返回
+        let ctx = ctx.with_payload(json!({"id": 70}));
+        assert_eq!(
+            approve.handle(&ctx).await,
+            Err(CommandError::Failed("order over limit".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extractor_failure_skips_handler() {
+        // 缺少 user_id 时处理器不会执行
+        let ctx = Context::new()
+            .with_run_id("run-2")
+            .with_payload(json!({"id": 1}))
+            .with_params(json!({"limit": 10}));
+        assert_eq!(
+            approve.handle(&ctx).await,
+            Err(CommandError::Extract("user_id not found".to_string()))
+        );
+
+        let ctx = ctx.with_user_id("user123").with_payload(json!({"id": "x"}));
+        assert!(matches!(
+            approve.handle(&ctx).await,
+            Err(CommandError::Extract(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_optional_extractor() {
+        assert!(whoami.handle(&Context::new()).await.is_err());
+        assert_eq!(
+            whoami.handle(&Context::new().with_user_id("user123")).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware_wraps_in_order() {
+        let trace = Arc::new(Mutex::new(vec![]));
+        let mut commander = Commander::new();
+        commander.register("demo", demo).unwrap();
+        commander.with_middleware(Trace("outer", trace.clone()));
+        commander.with_middleware(Timing);
+        commander.with_middleware(Trace("inner", trace.clone()));
+
+        commander.execute("demo", Context::new()).await.unwrap();
+        assert_eq!(
+            *trace.lock().unwrap(),
+            vec!["outer > demo", "inner > demo", "inner <", "outer <"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_require_user() {
+        let mut commander = Commander::new();
+        commander.register("demo", demo).unwrap();
+        commander.with_middleware(RequireUser);
+
+        assert!(matches!(
+            commander.execute("demo", Context::new()).await,
+            Err(CommandError::Unauthorized(_))
+        ));
+        assert_eq!(
+            commander
+                .execute("demo", Context::new().with_user_id("user123"))
+                .await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_id_propagated() {
+        let mut commander = Commander::new();
+        commander.register("record", record_request).unwrap();
+
+        // 没有中间件时缺少请求 id 会导致参数提取失败
+        assert!(commander.execute("record", Context::new()).await.is_err());
+
+        commander.with_middleware(RequestId);
+        commander
+            .execute("record", Context::new().with_request_id("req-42"))
+            .await
+            .unwrap();
+        assert!(SEEN.lock().unwrap().contains(&"request:req-42".to_string()));
+
+        // 缺少请求 id 时由中间件生成
+        assert_eq!(commander.execute("record", Context::new()).await, Ok(()));
+    }
 }
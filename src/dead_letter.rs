@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{handler2::TaskError, task::Task};
+
+// 重试退避的上限
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// 一次失败的投递
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttemptRecord {
+    /// 第几次投递
+    pub attempt: u32,
+
+    /// 处理器返回的错误
+    pub error: TaskError,
+
+    pub failed_at: DateTime<Utc>,
+}
+
+/// 死信: 超过最大投递次数或遇到不可重试错误而放弃的任务
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// 进入死信区时的任务，`data` 保持原始载荷
+    pub task: Task,
+
+    /// 最后一次失败的错误
+    pub last_error: TaskError,
+
+    /// 每次失败的记录，按投递先后排列
+    pub history: Vec<AttemptRecord>,
+
+    pub dead_at: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    /// 由任务和失败记录生成死信，没有失败记录时说明任务的租约多次到期未确认
+    pub fn new(task: Task, history: Vec<AttemptRecord>, dead_at: DateTime<Utc>) -> Self {
+        let last_error = match history.last() {
+            Some(record) => record.error.clone(),
+            None => TaskError::Retryable("超过最大投递次数".to_string()),
+        };
+        DeadLetter {
+            task,
+            last_error,
+            history,
+            dead_at,
+        }
+    }
+}
+
+/// 死信区的查看和处置
+#[async_trait]
+pub trait DeadLetterQueue {
+    /// 死信区中的任务，按进入先后排列
+    async fn dead_letters(&self) -> Vec<DeadLetter>;
+
+    /// 将死信重新入队，投递次数清零，返回任务是否在死信区中
+    async fn requeue_dead_letter(&self, task_id: &str) -> bool;
+
+    /// 删除一条死信，返回任务是否在死信区中
+    async fn purge_dead_letter(&self, task_id: &str) -> bool;
+
+    /// 清空死信区，返回删除的条数
+    async fn purge_dead_letters(&self) -> usize;
+}
+
+/// 第 `attempts` 次投递失败后的重试延迟: 以 `base` 为起点指数退避，最长 5 分钟
+pub fn retry_delay(base: Duration, attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    base.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
@@ -1,57 +1,716 @@
-use std::{collections::VecDeque, sync::Mutex};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use once_cell::sync::Lazy;
+use chrono::{DateTime, Utc};
+use tokio::sync::{Mutex, Notify};
 
-use crate::task::Task;
+use crate::{
+    dead_letter::{retry_delay, AttemptRecord, DeadLetter, DeadLetterQueue},
+    handler2::TaskError,
+    task::Task,
+};
 
+// 默认的重试延迟, 没有死信区的 fetcher 按此延迟归还失败的任务
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
 
+// 默认的幂等键去重窗口
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 任务获取器
+///
+/// 拉取到的任务处于租约中：在可见性超时之前不会再次投递。
+/// 处理完成后需调用 `ack` 确认，否则租约到期后任务会被重新投递。
+///
+/// `ack`、`nack`、`fail` 和 `extend_lease` 需要传入本次拉取到的任务，
+/// 任务的投递次数(`attempts`)标识这一次租约: 租约到期后任务被重新投递，
+/// 之前那次投递迟到的确认不会影响新的租约。
 #[async_trait]
 pub trait Fetcher {
-    async fn fetch(&self) -> Vec<Task>;
+    /// 拉取最多 `max` 个任务，没有任务时返回空数组
+    async fn fetch(&self, max: usize) -> Vec<Task>;
+
+    /// 只拉取 `task_types` 中类型的任务，`None` 表示任意类型。
+    /// 多个 worker 共享一个队列时，worker 据此只领取自己能处理的任务。
+    ///
+    /// 默认实现不支持按类型过滤，直接调用 `fetch`
+    async fn fetch_for(&self, max: usize, task_types: Option<&[String]>) -> Vec<Task> {
+        let _ = task_types;
+        self.fetch(max).await
+    }
+
+    /// 确认任务已处理完成，任务从队列中彻底移除
+    async fn ack(&self, task: &Task);
+
+    /// 放弃任务，任务在 `requeue_delay` 之后重新可见
+    async fn nack(&self, task: &Task, requeue_delay: Duration);
+
+    /// 续租: 处理时间较长的任务定期调用，避免租约到期后被重新投递。
+    /// 租约已失效(已确认或已被重新投递)时返回 false。
+    ///
+    /// 默认实现没有租约，总是返回 true
+    async fn extend_lease(&self, task: &Task) -> bool {
+        let _ = task;
+        true
+    }
+
+    /// 任务处理失败。支持死信区的 fetcher 应记录失败历史，可重试的错误退避后重新投递，
+    /// 用尽投递次数或不可重试的错误进入死信区。
+    ///
+    /// 默认实现没有死信区: 可重试的错误按固定延迟归还，不可重试的错误直接确认丢弃
+    async fn fail(&self, task: &Task, error: TaskError) {
+        if error.is_retryable() {
+            self.nack(task, DEFAULT_RETRY_DELAY).await;
+        } else {
+            println!("任务 {} 处理失败且不可重试, 丢弃: {}", task.id, error);
+            self.ack(task).await;
+        }
+    }
+
+    /// 拉取策略，worker 据此决定批量大小和空闲时的等待间隔
+    fn poll_strategy(&self) -> PollStrategy {
+        PollStrategy::default()
+    }
+
+    /// 推送模式：返回的 Notify 被触发时，worker 立即结束等待并重新拉取。
+    /// 生产者应使用 `notify_one`，这样在 worker 进入等待之前的通知也不会丢失
+    fn notifier(&self) -> Option<Arc<Notify>> {
+        None
+    }
+}
+
+/// 拉取策略：空闲间隔、空结果时的指数退避以及每次拉取的批量大小
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollStrategy {
+    /// 拉取到空结果后的初始等待间隔
+    pub idle_interval: Duration,
+
+    /// 连续空结果时等待间隔的上限
+    pub max_interval: Duration,
+
+    /// 每次空结果后等待间隔的放大倍数
+    pub backoff_factor: u32,
+
+    /// 每次拉取的最大任务数
+    pub batch_size: usize,
+}
+
+impl Default for PollStrategy {
+    fn default() -> Self {
+        PollStrategy {
+            idle_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(5),
+            backoff_factor: 2,
+            batch_size: 10,
+        }
+    }
 }
 
-pub struct LocalQueue<T> {
-    vec: VecDeque<T>,
+impl PollStrategy {
+    /// 根据当前等待间隔计算下一次空结果后的等待间隔
+    pub fn next_interval(&self, current: Duration) -> Duration {
+        current
+            .saturating_mul(self.backoff_factor.max(1))
+            .min(self.max_interval)
+    }
 }
 
-impl<T> LocalQueue<T> {
+// 就绪堆中的任务: 优先级高的先出, 同优先级按 run_after 和入队先后
+struct Ready {
+    seq: u64,
+    task: Task,
+}
+
+impl Ord for Ready {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.task
+            .priority
+            .cmp(&other.task.priority)
+            .then_with(|| other.task.run_after.cmp(&self.task.run_after))
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Ready {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ready {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for Ready {}
+
+// 延迟堆中的任务: run_after 早的先出
+struct Delayed(Ready);
+
+impl Ord for Delayed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .0
+            .task
+            .run_after
+            .cmp(&self.0.task.run_after)
+            .then_with(|| other.0.seq.cmp(&self.0.seq))
+    }
+}
+
+impl PartialOrd for Delayed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Delayed {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Delayed {}
+
+// 已投递但尚未确认的任务
+struct Leased {
+    seq: u64,
+    task: Task,
+    // 租约到期时间
+    visible_at: Instant,
+}
+
+/// 一次失败处理的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    /// 本次失败的记录
+    pub record: AttemptRecord,
+
+    /// 任务重新可见的时间, `None` 表示任务进入了死信区
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+/// 本地任务队列
+///
+/// 按优先级投递任务, `run_after` 未到的任务对 worker 不可见,
+/// 因此紧急任务可以越过积压的定时任务优先执行。
+///
+/// 失败的任务按指数退避重新投递, 超过最大投递次数或遇到不可重试错误的任务进入死信区。
+///
+/// 带幂等键的任务在去重窗口内只会入队一次, 即使之前的任务已经处理完成。
+pub struct LocalQueue {
+    // 已可投递的任务
+    ready: BinaryHeap<Ready>,
+
+    // 尚未到 run_after 的任务
+    delayed: BinaryHeap<Delayed>,
+
+    leased: HashMap<String, Leased>,
+
+    // 每个任务的失败记录
+    history: HashMap<String, Vec<AttemptRecord>>,
+
+    // 死信区, 按进入先后排列
+    dead: Vec<DeadLetter>,
+
+    // 幂等键到去重截止时间的映射
+    dedup: HashMap<String, DateTime<Utc>>,
+
+    // 按截止时间排列的幂等键, 用于清理过期的键
+    dedup_expiry: VecDeque<(DateTime<Utc>, String)>,
+
+    dedup_window: Duration,
+
+    // 入队序号, 同优先级的任务按入队先后投递
+    seq: u64,
+}
+
+impl LocalQueue {
     // 创建一个新的 LocalQueue
     pub fn new() -> Self {
         LocalQueue {
-            vec: VecDeque::new(),
+            ready: BinaryHeap::new(),
+            delayed: BinaryHeap::new(),
+            leased: HashMap::new(),
+            history: HashMap::new(),
+            dead: Vec::new(),
+            dedup: HashMap::new(),
+            dedup_expiry: VecDeque::new(),
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            seq: 0,
         }
     }
-    // 获取队列的大小
+
+    // 获取等待投递的任务数, 包括尚未到 run_after 的任务
     pub fn size(&self) -> usize {
-        self.vec.len()
+        self.ready.len() + self.delayed.len()
+    }
+
+    // 入队操作, 幂等键在去重窗口内重复时拒绝入队并返回 false
+    pub fn enqueue(&mut self, task: Task) -> bool {
+        self.prune_keys();
+        if self.is_duplicate(&task) {
+            println!("幂等键重复, 忽略任务: {:?}", task);
+            return false;
+        }
+        if let Some(key) = &task.idempotency_key {
+            let until = Utc::now() + self.dedup_window;
+            self.remember_key(key.clone(), until);
+        }
+        self.insert(task);
+        true
+    }
+
+    /// 任务的幂等键是否仍在去重窗口内
+    pub fn is_duplicate(&self, task: &Task) -> bool {
+        let now = Utc::now();
+        task.idempotency_key
+            .as_ref()
+            .and_then(|key| self.dedup.get(key))
+            .is_some_and(|until| *until > now)
+    }
+
+    /// 去重窗口
+    pub fn dedup_window(&self) -> Duration {
+        self.dedup_window
+    }
+
+    /// 设置去重窗口, 只影响之后入队的任务
+    pub fn set_dedup_window(&mut self, dedup_window: Duration) {
+        self.dedup_window = dedup_window;
+    }
+
+    // 出队操作, 取出当前可投递的优先级最高的任务
+    pub fn dequeue(&mut self) -> Option<Task> {
+        self.promote_delayed();
+        self.ready.pop().map(|ready| ready.task)
+    }
+
+    /// 租出最多 `max` 个任务，租约在 `visibility_timeout` 后到期。
+    /// 已用尽投递次数的任务(例如租约多次到期未确认)不再投递，直接进入死信区
+    pub fn lease(&mut self, max: usize, visibility_timeout: Duration) -> Vec<Task> {
+        self.lease_for(max, visibility_timeout, None)
+    }
+
+    /// 只租出 `task_types` 中类型的任务，`None` 表示任意类型
+    pub fn lease_for(
+        &mut self,
+        max: usize,
+        visibility_timeout: Duration,
+        task_types: Option<&[String]>,
+    ) -> Vec<Task> {
+        self.reclaim_expired();
+        self.promote_delayed();
+
+        let visible_at = Instant::now() + visibility_timeout;
+        let mut tasks = vec![];
+        // 类型不匹配的任务暂存后放回, 保留原有的入队序号
+        let mut skipped = vec![];
+        while tasks.len() < max {
+            let Some(Ready { seq, mut task }) = self.ready.pop() else {
+                break;
+            };
+            if task.attempts >= task.max_attempts {
+                println!("任务超过最大投递次数，进入死信区: {:?}", task);
+                self.bury(task, Utc::now());
+                continue;
+            }
+            if task_types.is_some_and(|task_types| !task_types.contains(&task.task_type)) {
+                skipped.push(Ready { seq, task });
+                continue;
+            }
+            task.mark_delivered();
+            tasks.push(task.clone());
+            self.leased.insert(
+                task.id.clone(),
+                Leased {
+                    seq,
+                    task,
+                    visible_at,
+                },
+            );
+        }
+        self.ready.extend(skipped);
+        tasks
+    }
+
+    /// 确认任务，返回任务的这次投递是否仍处于租约中
+    pub fn ack(&mut self, task: &Task) -> bool {
+        if self.take_lease(task).is_none() {
+            return false;
+        }
+        self.history.remove(&task.id);
+        true
     }
 
-    // 入队操作
-    pub fn enqueue(&mut self, e: T) {
-        self.vec.push_back(e);
+    /// 放弃任务，任务在 `requeue_delay` 之后重新可见，返回任务的这次投递是否仍处于租约中
+    pub fn nack(&mut self, task: &Task, requeue_delay: Duration) -> bool {
+        match self.take_lease(task) {
+            Some(Leased { seq, mut task, .. }) => {
+                task.mark_requeued(Utc::now() + requeue_delay);
+                self.push(seq, task);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 续租，租约在 `visibility_timeout` 后到期，返回任务的这次投递是否仍处于租约中
+    pub fn extend_lease(&mut self, task: &Task, visibility_timeout: Duration) -> bool {
+        match self.leased.get_mut(&task.id) {
+            Some(leased) if leased.task.attempts == task.attempts => {
+                leased.visible_at = Instant::now() + visibility_timeout;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 记录任务处理失败。可重试的错误在 `retry_base_delay` 起步的指数退避之后重新可见，
+    /// 用尽投递次数或不可重试的错误进入死信区。任务的这次投递不在租约中时返回 `None`
+    pub fn fail(
+        &mut self,
+        task: &Task,
+        error: TaskError,
+        retry_base_delay: Duration,
+    ) -> Option<Failure> {
+        let Leased { seq, mut task, .. } = self.take_lease(task)?;
+        let now = Utc::now();
+        let record = AttemptRecord {
+            attempt: task.attempts,
+            error: error.clone(),
+            failed_at: now,
+        };
+        self.history
+            .entry(task.id.clone())
+            .or_default()
+            .push(record.clone());
+
+        if !error.is_retryable() || task.attempts >= task.max_attempts {
+            println!("任务处理失败，进入死信区: {:?}, 错误: {}", task, error);
+            self.bury(task, now);
+            return Some(Failure {
+                record,
+                retry_at: None,
+            });
+        }
+
+        let retry_at = now + retry_delay(retry_base_delay, task.attempts);
+        task.mark_requeued(retry_at);
+        self.push(seq, task);
+        Some(Failure {
+            record,
+            retry_at: Some(retry_at),
+        })
     }
 
-    // 出队操作
-    pub fn dequeue(&mut self) -> Option<T> {
-        self.vec.pop_front()
+    /// 未确认的任务数
+    pub fn in_flight(&self) -> usize {
+        self.leased.len()
     }
+
+    /// 死信区中的任务
+    pub fn dead_letters(&self) -> &[DeadLetter] {
+        &self.dead
+    }
+
+    /// 将死信重新入队，投递次数和失败记录清零，返回重新入队的任务
+    pub fn requeue_dead_letter(&mut self, task_id: &str) -> Option<Task> {
+        let index = self.dead.iter().position(|dead| dead.task.id == task_id)?;
+        let mut task = self.dead.remove(index).task;
+        task.mark_revived();
+        // 死信重新入队是人工处置, 不受幂等键去重限制
+        self.insert(task.clone());
+        Some(task)
+    }
+
+    /// 删除一条死信，返回任务是否在死信区中
+    pub fn purge_dead_letter(&mut self, task_id: &str) -> bool {
+        let len = self.dead.len();
+        self.dead.retain(|dead| dead.task.id != task_id);
+        self.dead.len() < len
+    }
+
+    /// 清空死信区，返回删除的条数
+    pub fn purge_dead_letters(&mut self) -> usize {
+        std::mem::take(&mut self.dead).len()
+    }
+
+    // 恢复一个待投递的任务及其失败记录, 用于从持久化存储重建队列
+    pub(crate) fn restore(&mut self, task: Task, history: Vec<AttemptRecord>) {
+        if !history.is_empty() {
+            self.history.insert(task.id.clone(), history);
+        }
+        self.insert(task);
+    }
+
+    // 记录一个幂等键, 在 until 之前相同键的任务不能入队
+    pub(crate) fn remember_key(&mut self, key: String, until: DateTime<Utc>) {
+        self.dedup.insert(key.clone(), until);
+        self.dedup_expiry.push_back((until, key));
+    }
+
+    // 清理过期的幂等键
+    fn prune_keys(&mut self) {
+        let now = Utc::now();
+        while self
+            .dedup_expiry
+            .front()
+            .is_some_and(|(until, _)| *until <= now)
+        {
+            let (until, key) = self.dedup_expiry.pop_front().unwrap();
+            // 同一个键可能在过期后被重新记录, 只删除截止时间匹配的记录
+            if self.dedup.get(&key) == Some(&until) {
+                self.dedup.remove(&key);
+            }
+        }
+    }
+
+    // 分配入队序号并放入队列, 不做去重检查
+    fn insert(&mut self, task: Task) {
+        self.seq += 1;
+        self.push(self.seq, task);
+    }
+
+    // 恢复一条死信, 用于从持久化存储重建队列
+    pub(crate) fn restore_dead_letter(&mut self, dead: DeadLetter) {
+        self.dead.push(dead);
+    }
+
+    // 将任务连同失败记录移入死信区
+    fn bury(&mut self, mut task: Task, dead_at: DateTime<Utc>) {
+        let history = self.history.remove(&task.id).unwrap_or_default();
+        task.mark_failed();
+        self.dead.push(DeadLetter::new(task, history, dead_at));
+    }
+
+    // 按 run_after 放入就绪堆或延迟堆, 保留原有的入队序号
+    fn push(&mut self, seq: u64, task: Task) {
+        let ready = Ready { seq, task };
+        if ready.task.is_visible(Utc::now()) {
+            self.ready.push(ready);
+        } else {
+            self.delayed.push(Delayed(ready));
+        }
+    }
+
+    // 将已到 run_after 的任务移入就绪堆
+    fn promote_delayed(&mut self) {
+        let now = Utc::now();
+        while self
+            .delayed
+            .peek()
+            .is_some_and(|delayed| delayed.0.task.is_visible(now))
+        {
+            let Delayed(ready) = self.delayed.pop().unwrap();
+            self.ready.push(ready);
+        }
+    }
+
+    // 取出任务这次投递的租约, 投递次数不符说明是之前那次投递, 租约已失效
+    fn take_lease(&mut self, task: &Task) -> Option<Leased> {
+        if self
+            .leased
+            .get(&task.id)
+            .is_some_and(|leased| leased.task.attempts == task.attempts)
+        {
+            self.leased.remove(&task.id)
+        } else {
+            None
+        }
+    }
+
+    // 将租约到期的任务放回队列，保留原有的入队序号
+    fn reclaim_expired(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .leased
+            .iter()
+            .filter(|(_, leased)| leased.visible_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in expired {
+            if let Some(Leased { seq, mut task, .. }) = self.leased.remove(&id) {
+                task.mark_requeued(task.run_after);
+                self.push(seq, task);
+            }
+        }
+    }
+}
+
+impl Default for LocalQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 本地队列句柄，可在生产者和多个 worker 之间共享
+///
+/// 每个句柄背后是一个独立的队列，克隆句柄共享同一个队列。
+/// 入队会通知等待中的 worker，配合推送模式可以立即唤醒 worker。
+#[derive(Clone, Default)]
+pub struct LocalQueueHandle {
+    queue: Arc<Mutex<LocalQueue>>,
+    notifier: Arc<Notify>,
+}
+
+impl LocalQueueHandle {
+    /// 创建一个新的空队列
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建一个使用指定去重窗口的空队列
+    pub fn with_dedup_window(dedup_window: Duration) -> Self {
+        let mut queue = LocalQueue::new();
+        queue.set_dedup_window(dedup_window);
+        LocalQueueHandle {
+            queue: Arc::new(Mutex::new(queue)),
+            notifier: Arc::new(Notify::new()),
+        }
+    }
+
+    /// 任务入队，幂等键在去重窗口内重复时返回 false
+    pub async fn enqueue(&self, task: Task) -> bool {
+        let accepted = self.queue.lock().await.enqueue(task);
+        if accepted {
+            self.notifier.notify_one();
+        }
+        accepted
+    }
+
+    /// 批量入队，保持传入顺序，返回实际入队的任务数
+    pub async fn enqueue_batch(&self, tasks: impl IntoIterator<Item = Task> + Send) -> usize {
+        let mut queue = self.queue.lock().await;
+        let mut accepted = 0;
+        for task in tasks {
+            if queue.enqueue(task) {
+                accepted += 1;
+            }
+        }
+        if accepted > 0 {
+            self.notifier.notify_one();
+        }
+        accepted
+    }
+
+    /// 等待投递的任务数
+    pub async fn len(&self) -> usize {
+        self.queue.lock().await.size()
+    }
+
+    /// 是否没有等待投递的任务
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// 已投递但尚未确认的任务数
+    pub async fn in_flight(&self) -> usize {
+        self.queue.lock().await.in_flight()
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for LocalQueueHandle {
+    async fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.queue.lock().await.dead_letters().to_vec()
+    }
+
+    async fn requeue_dead_letter(&self, task_id: &str) -> bool {
+        let requeued = self.queue.lock().await.requeue_dead_letter(task_id);
+        if requeued.is_some() {
+            self.notifier.notify_one();
+        }
+        requeued.is_some()
+    }
+
+    async fn purge_dead_letter(&self, task_id: &str) -> bool {
+        self.queue.lock().await.purge_dead_letter(task_id)
+    }
+
+    async fn purge_dead_letters(&self) -> usize {
+        self.queue.lock().await.purge_dead_letters()
+    }
+}
+
+// 默认的可见性超时
+const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct LocalQueueFetcher {
+    queue: LocalQueueHandle,
+
+    pub poll_strategy: PollStrategy,
+
+    // 租约时长，超过该时间未确认的任务会被重新投递
+    pub visibility_timeout: Duration,
+
+    // 失败重试的起始延迟，之后每次失败翻倍
+    pub retry_delay: Duration,
 }
 
-// 实现 LocalQueue 的单例模式，专门用于 Task 类型
-pub static LOCAL_QUEUE_INSTANCE: Lazy<Mutex<LocalQueue<Task>>> =
-    Lazy::new(|| Mutex::new(LocalQueue::new()));
+impl LocalQueueFetcher {
+    /// 创建从指定队列拉取任务的获取器
+    pub fn new(queue: LocalQueueHandle) -> Self {
+        LocalQueueFetcher {
+            queue,
+            poll_strategy: PollStrategy::default(),
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
 
-pub struct LocalQueueFetcher;
+    /// 获取器使用的队列句柄
+    pub fn queue(&self) -> &LocalQueueHandle {
+        &self.queue
+    }
+}
 
 #[async_trait]
 impl Fetcher for LocalQueueFetcher {
-    async fn fetch(&self) -> Vec<Task> {
-        let mut queue = LOCAL_QUEUE_INSTANCE.lock().unwrap();
-        if let Some(task) = queue.dequeue() {
-            vec![task]
-        } else {
-            vec![]
+    async fn fetch(&self, max: usize) -> Vec<Task> {
+        self.fetch_for(max, None).await
+    }
+
+    async fn fetch_for(&self, max: usize, task_types: Option<&[String]>) -> Vec<Task> {
+        let mut queue = self.queue.queue.lock().await;
+        queue.lease_for(max, self.visibility_timeout, task_types)
+    }
+
+    async fn ack(&self, task: &Task) {
+        self.queue.queue.lock().await.ack(task);
+    }
+
+    async fn nack(&self, task: &Task, requeue_delay: Duration) {
+        let mut queue = self.queue.queue.lock().await;
+        if queue.nack(task, requeue_delay) && requeue_delay.is_zero() {
+            self.queue.notifier.notify_one();
+        }
+    }
+
+    async fn extend_lease(&self, task: &Task) -> bool {
+        let mut queue = self.queue.queue.lock().await;
+        queue.extend_lease(task, self.visibility_timeout)
+    }
+
+    async fn fail(&self, task: &Task, error: TaskError) {
+        let mut queue = self.queue.queue.lock().await;
+        let failure = queue.fail(task, error, self.retry_delay);
+        if failure.is_some_and(|failure| failure.retry_at.is_some()) && self.retry_delay.is_zero() {
+            self.queue.notifier.notify_one();
         }
     }
+
+    fn poll_strategy(&self) -> PollStrategy {
+        self.poll_strategy.clone()
+    }
+
+    fn notifier(&self) -> Option<Arc<Notify>> {
+        Some(self.queue.notifier.clone())
+    }
 }
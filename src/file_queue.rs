@@ -0,0 +1,586 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Mutex, Notify},
+};
+
+use crate::{
+    dead_letter::{AttemptRecord, DeadLetter, DeadLetterQueue},
+    fetcher::{Fetcher, LocalQueue, PollStrategy},
+    handler2::TaskError,
+    task::Task,
+};
+
+// 默认的可见性超时
+const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// 默认的失败重试起始延迟
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// 重放日志的结果: 待投递任务及其失败记录、死信、幂等键的去重截止时间
+type Replayed = (
+    Vec<(Task, Vec<AttemptRecord>)>,
+    Vec<DeadLetter>,
+    HashMap<String, DateTime<Utc>>,
+);
+
+/// 追加日志中的一条记录，每行一条 JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    // 任务入队, 带幂等键的任务同时记录去重截止时间
+    Enqueue {
+        task: Task,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dedup_until: Option<DateTime<Utc>>,
+    },
+    // 幂等键在 until 之前不能再次入队, 压缩日志时保留已完成任务的幂等键
+    Dedup {
+        key: String,
+        until: DateTime<Utc>,
+    },
+    // 任务被投递一次
+    Deliver {
+        id: String,
+    },
+    // 任务被放弃，在 run_after 之后重新可见
+    Requeue {
+        id: String,
+        run_after: DateTime<Utc>,
+    },
+    // 任务处理失败一次
+    Fail {
+        id: String,
+        record: AttemptRecord,
+    },
+    // 任务处理完成
+    Ack {
+        id: String,
+    },
+    // 任务进入死信区
+    Dead {
+        id: String,
+        at: DateTime<Utc>,
+    },
+    // 死信被删除(或重新入队)
+    Purge {
+        id: String,
+    },
+}
+
+// 追加日志文件。写入和 fsync 由 tokio 放到 blocking 线程池执行，不会阻塞运行时的工作线程
+struct LogFile(tokio::fs::File);
+
+impl LogFile {
+    // 追加一条记录并落盘，调用方持有 state 锁直到写完，保证日志顺序与内存状态一致
+    async fn append(&mut self, record: &LogRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.0.write_all(&line).await?;
+        // tokio 的 write_all 只是提交后台写入, flush 等待写入完成并返回写入错误,
+        // 否则 sync_data 会在写入失败时仍然返回 Ok
+        self.0.flush().await?;
+        self.0.sync_data().await
+    }
+
+    // 追加一条记录，失败时只打印错误: 内存中的队列已经更新，重启后最多重复投递
+    async fn append_logged(&mut self, record: &LogRecord) {
+        if let Err(err) = self.append(record).await {
+            println!("写入任务日志失败: {}", err);
+        }
+    }
+}
+
+struct FileQueueState {
+    log: LogFile,
+
+    // 待投递、投递中的任务和死信区
+    queue: LocalQueue,
+}
+
+// 已租出但尚未交给调用方的任务。拉取在写日志时被取消(例如 worker 停机)会 drop 它,
+// 任务立即放回队列，而不是一直占着租约直到可见性超时
+struct PendingLease<'a> {
+    queue: &'a mut LocalQueue,
+    tasks: Vec<Task>,
+}
+
+impl PendingLease<'_> {
+    // 任务已交给调用方, 不再归还
+    fn into_tasks(mut self) -> Vec<Task> {
+        std::mem::take(&mut self.tasks)
+    }
+}
+
+impl Drop for PendingLease<'_> {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            self.queue.nack(task, Duration::ZERO);
+        }
+    }
+}
+
+/// 基于追加日志的持久化任务队列
+///
+/// 入队、投递、失败、确认和死信都会追加到日志文件，进程重启后通过重放日志恢复队列，
+/// 单机部署不需要外部消息中间件。已投递但未确认的任务在重启后会被重新投递，
+/// 投递次数超过任务 `max_attempts` 或遇到不可重试错误的任务进入死信区，不再投递。
+pub struct FileQueue {
+    path: PathBuf,
+
+    state: Mutex<FileQueueState>,
+
+    notifier: Arc<Notify>,
+
+    pub poll_strategy: PollStrategy,
+
+    // 租约时长，超过该时间未确认的任务会被重新投递
+    pub visibility_timeout: Duration,
+
+    // 失败重试的起始延迟，之后每次失败翻倍
+    pub retry_delay: Duration,
+}
+
+impl FileQueue {
+    /// 打开(或创建)日志文件，重放并压缩日志
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (pending, dead, keys) = Self::replay(&path)?;
+
+        // 只保留仍然有效的记录，避免日志无限增长
+        let now = Utc::now();
+        let mut keys: Vec<(String, DateTime<Utc>)> =
+            keys.into_iter().filter(|(_, until)| *until > now).collect();
+        keys.sort_by_key(|(_, until)| *until);
+        let log = Self::compact(&path, &pending, &dead, &keys)?;
+
+        let mut queue = LocalQueue::new();
+        for (key, until) in keys {
+            queue.remember_key(key, until);
+        }
+        for dead in dead {
+            queue.restore_dead_letter(dead);
+        }
+        for (task, history) in pending {
+            queue.restore(task, history);
+        }
+
+        Ok(FileQueue {
+            path,
+            state: Mutex::new(FileQueueState {
+                log: LogFile(tokio::fs::File::from_std(log)),
+                queue,
+            }),
+            notifier: Arc::new(Notify::new()),
+            poll_strategy: PollStrategy::default(),
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        })
+    }
+
+    /// 日志文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 任务入队，写入日志后才对 worker 可见。
+    /// 幂等键在去重窗口内重复时不入队，返回 `Ok(false)`
+    pub async fn enqueue(&self, task: Task) -> io::Result<bool> {
+        let mut state = self.state.lock().await;
+        if state.queue.is_duplicate(&task) {
+            println!("幂等键重复, 忽略任务: {:?}", task);
+            return Ok(false);
+        }
+        let dedup_until = task
+            .idempotency_key
+            .as_ref()
+            .map(|_| Utc::now() + state.queue.dedup_window());
+        state
+            .log
+            .append(&LogRecord::Enqueue {
+                task: task.clone(),
+                dedup_until,
+            })
+            .await?;
+        state.queue.enqueue(task);
+        self.notifier.notify_one();
+        Ok(true)
+    }
+
+    /// 设置幂等键的去重窗口，只影响之后入队的任务
+    pub async fn set_dedup_window(&self, dedup_window: Duration) {
+        self.state.lock().await.queue.set_dedup_window(dedup_window);
+    }
+
+    /// 等待投递的任务数，包括尚未到 run_after 的任务
+    pub async fn len(&self) -> usize {
+        self.state.lock().await.queue.size()
+    }
+
+    /// 是否没有等待投递的任务
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    // 重放日志，得到待投递任务(按入队顺序)、死信和幂等键
+    fn replay(path: &Path) -> io::Result<Replayed> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok((vec![], vec![], HashMap::new()))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut order: Vec<String> = vec![];
+        let mut tasks: HashMap<String, Task> = HashMap::new();
+        let mut history: HashMap<String, Vec<AttemptRecord>> = HashMap::new();
+        let mut dead: Vec<DeadLetter> = vec![];
+        let mut keys: HashMap<String, DateTime<Utc>> = HashMap::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // 进程崩溃时最后一行可能只写了一半，忽略无法解析的记录
+            let record: LogRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(err) => {
+                    println!("忽略无法解析的日志记录: {}", err);
+                    continue;
+                }
+            };
+
+            match record {
+                LogRecord::Enqueue { task, dedup_until } => {
+                    if let (Some(key), Some(until)) = (&task.idempotency_key, dedup_until) {
+                        keys.insert(key.clone(), until);
+                    }
+                    order.push(task.id.clone());
+                    history.remove(&task.id);
+                    tasks.insert(task.id.clone(), task);
+                }
+                LogRecord::Dedup { key, until } => {
+                    keys.insert(key, until);
+                }
+                LogRecord::Deliver { id } => {
+                    if let Some(task) = tasks.get_mut(&id) {
+                        task.attempts += 1;
+                    }
+                }
+                LogRecord::Requeue { id, run_after } => {
+                    if let Some(task) = tasks.get_mut(&id) {
+                        task.run_after = run_after;
+                    }
+                }
+                LogRecord::Fail { id, record } => {
+                    if tasks.contains_key(&id) {
+                        history.entry(id).or_default().push(record);
+                    }
+                }
+                LogRecord::Ack { id } => {
+                    tasks.remove(&id);
+                    history.remove(&id);
+                }
+                LogRecord::Dead { id, at } => {
+                    if let Some(mut task) = tasks.remove(&id) {
+                        task.mark_failed();
+                        let history = history.remove(&id).unwrap_or_default();
+                        dead.push(DeadLetter::new(task, history, at));
+                    }
+                }
+                LogRecord::Purge { id } => dead.retain(|dead| dead.task.id != id),
+            }
+        }
+
+        let pending = order
+            .into_iter()
+            .filter_map(|id| {
+                let task = tasks.remove(&id)?;
+                let history = history.remove(&id).unwrap_or_default();
+                Some((task, history))
+            })
+            .collect();
+        Ok((pending, dead, keys))
+    }
+
+    // 用当前状态重写日志，写入临时文件后替换，返回追加模式打开的日志
+    fn compact(
+        path: &Path,
+        pending: &[(Task, Vec<AttemptRecord>)],
+        dead: &[DeadLetter],
+        keys: &[(String, DateTime<Utc>)],
+    ) -> io::Result<File> {
+        // 任务本身记录了投递次数和 run_after，失败记录需要单独写入
+        let task_records = |task: &Task, history: &[AttemptRecord]| {
+            let mut records = vec![LogRecord::Enqueue {
+                task: task.clone(),
+                dedup_until: None,
+            }];
+            records.extend(history.iter().map(|record| LogRecord::Fail {
+                id: task.id.clone(),
+                record: record.clone(),
+            }));
+            records
+        };
+
+        let mut records: Vec<LogRecord> = keys
+            .iter()
+            .map(|(key, until)| LogRecord::Dedup {
+                key: key.clone(),
+                until: *until,
+            })
+            .collect();
+        for dead in dead {
+            records.extend(task_records(&dead.task, &dead.history));
+            records.push(LogRecord::Dead {
+                id: dead.task.id.clone(),
+                at: dead.dead_at,
+            });
+        }
+        for (task, history) in pending {
+            records.extend(task_records(task, history));
+        }
+
+        let tmp_path = path.with_extension("compact");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            for record in &records {
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                tmp.write_all(&line)?;
+            }
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+
+        OpenOptions::new().append(true).open(path)
+    }
+}
+
+#[async_trait]
+impl Fetcher for FileQueue {
+    async fn fetch(&self, max: usize) -> Vec<Task> {
+        self.fetch_for(max, None).await
+    }
+
+    async fn fetch_for(&self, max: usize, task_types: Option<&[String]>) -> Vec<Task> {
+        let mut state = self.state.lock().await;
+        let FileQueueState { log, queue } = &mut *state;
+
+        // lease 会把用尽投递次数的任务移入死信区，新增的死信同样需要落盘
+        let buried = queue.dead_letters().len();
+        let leased = queue.lease_for(max, self.visibility_timeout, task_types);
+        let dead: Vec<LogRecord> = queue.dead_letters()[buried..]
+            .iter()
+            .map(|dead| LogRecord::Dead {
+                id: dead.task.id.clone(),
+                at: dead.dead_at,
+            })
+            .collect();
+
+        // 从这里开始的 await 都可能被取消, 租出的任务由 PendingLease 负责归还
+        let mut lease = PendingLease {
+            queue,
+            tasks: leased,
+        };
+        for record in &dead {
+            log.append_logged(record).await;
+        }
+
+        let mut delivered = 0;
+        while delivered < lease.tasks.len() {
+            let record = LogRecord::Deliver {
+                id: lease.tasks[delivered].id.clone(),
+            };
+            if let Err(err) = log.append(&record).await {
+                println!("写入任务日志失败: {}", err);
+                let task = lease.tasks.remove(delivered);
+                lease.queue.nack(&task, Duration::ZERO);
+                continue;
+            }
+            delivered += 1;
+        }
+        lease.into_tasks()
+    }
+
+    async fn ack(&self, task: &Task) {
+        let mut state = self.state.lock().await;
+        if !state.queue.ack(task) {
+            return;
+        }
+        state
+            .log
+            .append_logged(&LogRecord::Ack {
+                id: task.id.clone(),
+            })
+            .await;
+    }
+
+    async fn nack(&self, task: &Task, requeue_delay: Duration) {
+        let mut state = self.state.lock().await;
+        if !state.queue.nack(task, requeue_delay) {
+            return;
+        }
+        state
+            .log
+            .append_logged(&LogRecord::Requeue {
+                id: task.id.clone(),
+                run_after: Utc::now() + requeue_delay,
+            })
+            .await;
+        if requeue_delay.is_zero() {
+            self.notifier.notify_one();
+        }
+    }
+
+    // 租约只保存在内存中, 重启后未确认的任务总会重新投递, 续租不需要写日志
+    async fn extend_lease(&self, task: &Task) -> bool {
+        let mut state = self.state.lock().await;
+        state.queue.extend_lease(task, self.visibility_timeout)
+    }
+
+    async fn fail(&self, task: &Task, error: TaskError) {
+        let mut state = self.state.lock().await;
+        let Some(failure) = state.queue.fail(task, error, self.retry_delay) else {
+            return;
+        };
+        let id = task.id.clone();
+        state
+            .log
+            .append_logged(&LogRecord::Fail {
+                id: id.clone(),
+                record: failure.record.clone(),
+            })
+            .await;
+        match failure.retry_at {
+            Some(run_after) => {
+                state
+                    .log
+                    .append_logged(&LogRecord::Requeue { id, run_after })
+                    .await
+            }
+            None => {
+                state
+                    .log
+                    .append_logged(&LogRecord::Dead {
+                        id,
+                        at: failure.record.failed_at,
+                    })
+                    .await
+            }
+        }
+    }
+
+    fn poll_strategy(&self) -> PollStrategy {
+        self.poll_strategy.clone()
+    }
+
+    fn notifier(&self) -> Option<Arc<Notify>> {
+        Some(self.notifier.clone())
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for FileQueue {
+    async fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.state.lock().await.queue.dead_letters().to_vec()
+    }
+
+    async fn requeue_dead_letter(&self, task_id: &str) -> bool {
+        let mut state = self.state.lock().await;
+        let Some(task) = state.queue.requeue_dead_letter(task_id) else {
+            return false;
+        };
+        // 先删除死信再以清零后的状态重新入队
+        state
+            .log
+            .append_logged(&LogRecord::Purge {
+                id: task_id.to_string(),
+            })
+            .await;
+        state
+            .log
+            .append_logged(&LogRecord::Enqueue {
+                task,
+                dedup_until: None,
+            })
+            .await;
+        self.notifier.notify_one();
+        true
+    }
+
+    async fn purge_dead_letter(&self, task_id: &str) -> bool {
+        let mut state = self.state.lock().await;
+        if !state.queue.purge_dead_letter(task_id) {
+            return false;
+        }
+        state
+            .log
+            .append_logged(&LogRecord::Purge {
+                id: task_id.to_string(),
+            })
+            .await;
+        true
+    }
+
+    async fn purge_dead_letters(&self) -> usize {
+        let mut state = self.state.lock().await;
+        let ids: Vec<String> = state
+            .queue
+            .dead_letters()
+            .iter()
+            .map(|dead| dead.task.id.clone())
+            .collect();
+        for id in &ids {
+            state
+                .log
+                .append_logged(&LogRecord::Purge { id: id.clone() })
+                .await;
+        }
+        state.queue.purge_dead_letters()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failed_write_is_reported() {
+        let path =
+            std::env::temp_dir().join(format!("autoflow-failed-write-{}.log", nanoid::nanoid!(8)));
+        let queue = FileQueue::open(&path).unwrap();
+        let task = Task::new("test".to_string(), json!({}));
+        queue.enqueue(task.clone()).await.unwrap();
+        let fetched = queue.fetch(1).await;
+
+        // 换成只读打开的日志文件, 之后的每次写入都会失败
+        queue.state.lock().await.log = LogFile(tokio::fs::File::open(&path).await.unwrap());
+
+        let other = Task::new("test".to_string(), json!({}));
+        assert!(queue.enqueue(other).await.is_err());
+        assert_eq!(queue.len().await, 0);
+
+        let ack = LogRecord::Ack {
+            id: fetched[0].id.clone(),
+        };
+        assert!(queue.state.lock().await.log.append(&ack).await.is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
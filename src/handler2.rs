@@ -1,16 +1,47 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::task::Task;
+
+/// 任务处理失败的原因
+///
+/// 可重试的错误会在退避之后重新投递，直到用尽任务的最大投递次数；
+/// 不可重试的错误直接进入死信区。
+#[derive(Debug, Clone, Error, PartialEq, Serialize, Deserialize)]
+pub enum TaskError {
+    #[error("可重试错误: {0}")]
+    Retryable(String),
+
+    #[error("不可重试错误: {0}")]
+    Fatal(String),
+}
+
+impl TaskError {
+    /// 是否可以重试
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, TaskError::Retryable(_))
+    }
+}
 
 #[async_trait]
 pub trait TaskHandler: Send + Sync {
     fn for_task(&self) -> &'static str;
 
+    /// 处理器的名称，用于日志和展示，默认与任务类型相同
+    fn name(&self) -> &'static str {
+        self.for_task()
+    }
+
     /// 在处理任务之前调用的钩子
     async fn before(&self) {
         // 默认实现为空，可以被覆盖
     }
 
     /// 处理任务的主要逻辑，必须被实现
-    async fn handle(&self);
+    async fn handle(&self, task: &Task) -> Result<(), TaskError>;
 
     /// 在处理任务之后调用的钩子
     async fn after(&self) {
@@ -18,19 +49,74 @@ pub trait TaskHandler: Send + Sync {
     }
 }
 
+/// 共享的处理器同样是处理器，注册表中的 `Arc<dyn TaskHandler>` 可以直接交给 worker
+#[async_trait]
+impl<T: TaskHandler + ?Sized> TaskHandler for Arc<T> {
+    fn for_task(&self) -> &'static str {
+        (**self).for_task()
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    async fn before(&self) {
+        (**self).before().await
+    }
+
+    async fn handle(&self, task: &Task) -> Result<(), TaskError> {
+        (**self).handle(task).await
+    }
+
+    async fn after(&self) {
+        (**self).after().await
+    }
+}
+
 /// 扩展 Trait，用于提供 exec 方法
 #[async_trait]
 pub trait TaskHandlerExec {
-    /// 执行任务处理流程：before -> handle -> after
-    async fn exec(&self);
+    /// 执行任务处理流程：before -> handle -> after，返回 handle 的结果
+    async fn exec(&self, task: &Task) -> Result<(), TaskError>;
 }
 
 /// 为所有实现了 TaskHandler 的类型提供 exec 方法
 #[async_trait]
 impl<T: TaskHandler + Sync> TaskHandlerExec for T {
-    async fn exec(&self) {
+    async fn exec(&self, task: &Task) -> Result<(), TaskError> {
         self.before().await; // 确保异步方法在此被执行
-        self.handle().await; // 同样对 handle 使用 await
+        let result = self.handle(task).await; // 同样对 handle 使用 await
         self.after().await; // 确保 after 执行
+        result
+    }
+}
+
+/// 从任务数据中按字段名取出处理函数的参数，`#[task]` 宏展开后的代码使用
+///
+/// 缺少的字段按 `null` 解析，因此 `Option` 类型的参数可以省略；解析失败属于不可重试的错误
+pub fn extract_arg<T: DeserializeOwned>(task: &Task, field: &str) -> Result<T, TaskError> {
+    let value = task
+        .data
+        .get(field)
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    serde_json::from_value(value)
+        .map_err(|err| TaskError::Fatal(format!("参数 '{}' 解析失败: {}", field, err)))
+}
+
+/// 处理函数的返回值到处理结果的转换，`#[task]` 宏展开后的代码使用
+pub trait IntoTaskResult {
+    fn into_task_result(self) -> Result<(), TaskError>;
+}
+
+impl IntoTaskResult for () {
+    fn into_task_result(self) -> Result<(), TaskError> {
+        Ok(())
+    }
+}
+
+impl<T, E: Into<TaskError>> IntoTaskResult for Result<T, E> {
+    fn into_task_result(self) -> Result<(), TaskError> {
+        self.map(|_| ()).map_err(Into::into)
     }
 }
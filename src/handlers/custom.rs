@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use crate::handler2::TaskHandler;
+use crate::{
+    handler2::{TaskError, TaskHandler},
+    task::Task,
+};
 
 pub struct CustomTaskHandler {}
 
@@ -15,8 +18,9 @@ impl TaskHandler for CustomTaskHandler {
         println!("完成任务");
     }
 
-    async fn handle(&self) {
+    async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
         println!("正在处理任务数据");
+        Ok(())
     }
     
     fn for_task (&self) -> &'static str {
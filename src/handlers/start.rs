@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use crate::handler2::TaskHandler;
+use crate::{
+    handler2::{TaskError, TaskHandler},
+    task::Task,
+};
 
 pub struct StartTaskHandler {}
 
@@ -15,11 +18,12 @@ impl TaskHandler for StartTaskHandler {
         println!("完成任务");
     }
 
-    async fn handle(&self) {
+    async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
         println!("正在处理任务数据");
+        Ok(())
     }
     
     fn for_task (&self) -> &'static str {
-        "custom"
+        "start"
     }
 }
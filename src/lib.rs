@@ -1,3 +1,6 @@
+// 让 #[task] 宏展开后的 ::autoflow 路径在本 crate 内同样可用
+extern crate self as autoflow;
+
 pub mod edge;
 pub mod node;
 // pub mod node_manager;
@@ -17,7 +20,24 @@ pub mod defaults;
 pub mod enums;
 pub mod planner;
 pub mod fetcher;
+pub mod dead_letter;
+pub mod file_queue;
 pub mod task;
 pub mod handlers;
+pub mod registry;
 pub mod reactflow;
-pub mod commander;
\ No newline at end of file
+pub mod commander;
+pub mod trigger_executor;
+
+pub use codegen::{task, WorkflowNode};
+
+// 供 #[task] 和 #[derive(WorkflowNode)] 宏展开后的代码使用, 不属于公开 API
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::handler2::{extract_arg, IntoTaskResult};
+    pub use arrow;
+    pub use async_trait::async_trait;
+    pub use inventory;
+    pub use schemars;
+    pub use serde_json;
+}
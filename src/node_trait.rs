@@ -13,11 +13,17 @@ use std::error::Error;
 /// 7. 每个实现了NodeTrait的自定义节点的execute方法内可以方便的使用NodeTrait默认提供的xxx方法读取输入端点的参数
 /// 8. 每个实现了NodeTrait的自定义节点的execute方法内可以方便的使用NodeTrait默认提供的xxx方法读取输入端点的参数
 /// 9. 每个实现了NodeTrait的自定义节点的execute方法内部的每一个输出都是一个独立的arrow数据格式
+/// 10. 除execute之外的声明部分(NodeDefinition)可以通过 `#[derive(WorkflowNode)]` 生成
 /// 
-pub trait NodeTrait {
+pub trait NodeTrait: NodeDefinition {
     /// 每个节点必须实现的 `execute` 方法，用于执行节点的计算逻辑
     fn execute(&mut self) -> Result<HashMap<String, RecordBatch>, Box<dyn Error>>;
+}
 
+/// 节点的声明部分：端点、参数的 schema 和端点数据的存取
+///
+/// 通常由 `#[derive(WorkflowNode)]` 生成，自定义节点只需要实现 `NodeTrait::execute`
+pub trait NodeDefinition {
     /// 获取输入端点的 Arrow 数据，输入端点的名称为 key
     fn get_input(&self, key: &str) -> Option<&RecordBatch>;
 
@@ -44,6 +50,7 @@ pub trait NodeTrait {
 }
 
 /// 假设一个枚举类型表示不同的 Arrow 数据类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArrowType {
     Int32,
     Float64,
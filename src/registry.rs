@@ -0,0 +1,112 @@
+use std::{collections::HashMap, sync::Arc};
+
+use thiserror::Error;
+
+use crate::{handler2::TaskHandler, handlers::HANDLERS};
+
+/// 注册处理器时的错误
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum RegistryError {
+    #[error("任务类型 '{0}' 重复注册")]
+    Duplicate(String),
+}
+
+/// 链接时登记的处理器
+///
+/// `#[task]` 宏会为每个处理器提交一条登记，手写的处理器也可以通过
+/// `inventory::submit!` 登记。下游 crate 中的登记同样会被收集。
+pub struct HandlerRegistration {
+    handler: fn() -> Arc<dyn TaskHandler + Send + Sync>,
+}
+
+impl HandlerRegistration {
+    pub const fn new(handler: fn() -> Arc<dyn TaskHandler + Send + Sync>) -> Self {
+        HandlerRegistration { handler }
+    }
+
+    /// 创建登记的处理器
+    pub fn handler(&self) -> Arc<dyn TaskHandler + Send + Sync> {
+        (self.handler)()
+    }
+}
+
+inventory::collect!(HandlerRegistration);
+
+/// 任务处理器注册表，以 `TaskHandler::for_task` 作为任务类型
+///
+/// 同一个任务类型只能注册一个处理器，重复注册在启动时就会报错，
+/// 避免后注册的处理器悄悄覆盖前一个。
+#[derive(Clone, Default)]
+pub struct HandlerRegistry {
+    handlers: HashMap<String, Arc<dyn TaskHandler + Send + Sync>>,
+}
+
+impl HandlerRegistry {
+    /// 创建一个空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由一组处理器创建注册表
+    pub fn from_handlers(
+        handlers: impl IntoIterator<Item = Arc<dyn TaskHandler + Send + Sync>>,
+    ) -> Result<Self, RegistryError> {
+        let mut registry = Self::new();
+        for handler in handlers {
+            registry.register(handler)?;
+        }
+        Ok(registry)
+    }
+
+    /// 编译进当前程序的所有登记的处理器，包括 `#[task]` 生成的处理器
+    pub fn discovered() -> Result<Self, RegistryError> {
+        Self::from_handlers(
+            inventory::iter::<HandlerRegistration>().map(HandlerRegistration::handler),
+        )
+    }
+
+    /// 内置的处理器，即 `handlers::HANDLERS`
+    pub fn builtin() -> Result<Self, RegistryError> {
+        Self::from_handlers(HANDLERS.iter().cloned())
+    }
+
+    /// 注册一个处理器，任务类型已存在时返回错误
+    pub fn register(
+        &mut self,
+        handler: Arc<dyn TaskHandler + Send + Sync>,
+    ) -> Result<(), RegistryError> {
+        let task_type = handler.for_task();
+        if self.handlers.contains_key(task_type) {
+            return Err(RegistryError::Duplicate(task_type.to_string()));
+        }
+        self.handlers.insert(task_type.to_string(), handler);
+        Ok(())
+    }
+
+    /// 任务类型对应的处理器
+    pub fn get(&self, task_type: &str) -> Option<Arc<dyn TaskHandler + Send + Sync>> {
+        self.handlers.get(task_type).cloned()
+    }
+
+    /// 已注册的任务类型，按字典序排列
+    pub fn task_types(&self) -> Vec<String> {
+        let mut task_types: Vec<String> = self.handlers.keys().cloned().collect();
+        task_types.sort();
+        task_types
+    }
+
+    /// 已注册的处理器个数
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    /// 是否没有注册任何处理器
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// 遍历任务类型和处理器
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Arc<dyn TaskHandler + Send + Sync>)> {
+        self.handlers.iter()
+    }
+}
@@ -1,5 +1,12 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
 
+// 默认的最大尝试次数
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
 // 任务状态的枚举类型
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum TaskStatus {
@@ -11,20 +18,124 @@ pub enum TaskStatus {
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Task {
+    // 任务唯一标识, 用于确认(ack)和归还(nack)
+    #[serde(default = "new_id")]
+    pub id: String,
     pub task_type: String,
     // 当前任务要处理的数据
     pub data: serde_json::Value,
     // 当前任务状态
     pub status: TaskStatus,
+    // 优先级, 数值越大越先投递
+    #[serde(default)]
+    pub priority: i32,
+    // 最早可投递时间, 在此之前任务对 worker 不可见
+    #[serde(default = "Utc::now")]
+    pub run_after: DateTime<Utc>,
+    // 已投递的次数
+    #[serde(default)]
+    pub attempts: u32,
+    // 最大投递次数
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    // 幂等键, 去重窗口内相同键的任务只会入队一次
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+// 旧版本序列化的任务没有以下字段, 反序列化时使用默认值
+fn new_id() -> String {
+    nanoid!()
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_MAX_ATTEMPTS
 }
 
 impl Task {
     // 新建任务的构造函数
     pub fn new(task_type: String, data: serde_json::Value) -> Self {
+        let now = Utc::now();
         Task {
+            id: new_id(),
             task_type,
             data,
             status: TaskStatus::Queued,
+            priority: 0,
+            run_after: now,
+            attempts: 0,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            idempotency_key: None,
+            created_at: now,
+            updated_at: now,
         }
     }
+
+    /// 设置优先级, 数值越大越先投递
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 设置最早可投递时间
+    pub fn with_run_after(mut self, run_after: DateTime<Utc>) -> Self {
+        self.run_after = run_after;
+        self
+    }
+
+    /// 延迟一段时间后才可投递
+    pub fn with_delay(self, delay: Duration) -> Self {
+        let run_after = self.created_at + delay;
+        self.with_run_after(run_after)
+    }
+
+    /// 设置最大投递次数
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// 设置幂等键, 重试的请求或重放的触发器使用相同的键就不会重复执行
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    /// 当前是否可以投递
+    pub fn is_visible(&self, now: DateTime<Utc>) -> bool {
+        self.run_after <= now
+    }
+
+    // 记录一次投递
+    pub(crate) fn mark_delivered(&mut self) {
+        self.attempts += 1;
+        self.status = TaskStatus::Running;
+        self.updated_at = Utc::now();
+    }
+
+    // 放弃重试, 进入死信区
+    pub(crate) fn mark_failed(&mut self) {
+        self.status = TaskStatus::Failed;
+        self.updated_at = Utc::now();
+    }
+
+    // 从死信区重新入队, 投递次数清零并立即可见
+    pub(crate) fn mark_revived(&mut self) {
+        let now = Utc::now();
+        self.attempts = 0;
+        self.status = TaskStatus::Queued;
+        self.run_after = now;
+        self.updated_at = now;
+    }
+
+    // 重新排队, 在 run_after 之后可见
+    pub(crate) fn mark_requeued(&mut self, run_after: DateTime<Utc>) {
+        self.status = TaskStatus::Queued;
+        self.run_after = run_after;
+        self.updated_at = Utc::now();
+    }
 }
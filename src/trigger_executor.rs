@@ -0,0 +1,205 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix::Recipient;
+use async_trait::async_trait;
+use schedule::scheduler::RunCompleted;
+use serde_json::json;
+use tokio::sync::{mpsc, Notify};
+
+use crate::{
+    fetcher::{Fetcher, LocalQueueHandle, PollStrategy},
+    handler2::TaskError,
+    task::Task,
+};
+
+/// 触发器触发后入队的任务类型，worker 需要为它注册处理器
+pub const WORKFLOW_TASK_TYPE: &str = "workflow";
+
+// 调度器已取消但 worker 尚未开始的运行, 以调度任务的 id 标识
+type CancelledRuns = Arc<Mutex<HashSet<String>>>;
+
+/// 把 `schedule::scheduler::Scheduler` 触发的任务转成 worker 队列中的任务
+///
+/// 每次触发入队一个任务，数据中包含 `workflow_id`、`trigger_id` 和 `scheduled_at`，
+/// 并以调度任务的 id 作为幂等键，同一次触发重复投递只会入队一次。
+///
+/// 重叠策略不是 `Allow` 的触发器需要知道每次运行何时结束，
+/// worker 应使用 `completion_fetcher` 包装后的 fetcher，见 `CompletionFetcher`。
+///
+/// `Scheduler` 的执行是同步的，入队通过通道交给后台任务完成，
+/// 因此需要在 tokio 运行时（包括 actix 的运行时）中创建。
+pub struct QueueExecutor {
+    task_type: String,
+    sender: mpsc::UnboundedSender<Task>,
+    cancelled: CancelledRuns,
+}
+
+impl QueueExecutor {
+    pub fn new(queue: LocalQueueHandle) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Task>();
+        // 按触发顺序入队，执行器被丢弃后自动退出
+        tokio::spawn(async move {
+            while let Some(task) = receiver.recv().await {
+                queue.enqueue(task).await;
+            }
+        });
+        QueueExecutor {
+            task_type: WORKFLOW_TASK_TYPE.to_string(),
+            sender,
+            cancelled: CancelledRuns::default(),
+        }
+    }
+
+    /// 设置入队任务的类型，默认为 `WORKFLOW_TASK_TYPE`
+    pub fn with_task_type(mut self, task_type: impl Into<String>) -> Self {
+        self.task_type = task_type.into();
+        self
+    }
+
+    /// 调度任务对应的 worker 任务
+    pub fn to_task(&self, scheduled: &schedule::task::Task) -> Task {
+        Task::new(
+            self.task_type.clone(),
+            json!({
+                "workflow_id": scheduled.workflow_id,
+                "trigger_id": scheduled.trigger_id,
+                "scheduled_at": scheduled.run_at,
+            }),
+        )
+        .with_idempotency_key(scheduled.id.clone())
+    }
+
+    /// 包装 worker 使用的 fetcher，运行结束后向 `scheduler` 报告 `RunCompleted`
+    ///
+    /// 执行器在创建调度器时就被移入，通常在 `Scheduler::create` 中同时创建 fetcher:
+    ///
+    /// ```ignore
+    /// let mut fetcher = None;
+    /// let scheduler = Scheduler::create(|ctx| {
+    ///     let executor = QueueExecutor::new(queue.clone());
+    ///     fetcher = Some(executor.completion_fetcher(inner, ctx.address().recipient()));
+    ///     Scheduler::new(executor)
+    /// });
+    /// ```
+    pub fn completion_fetcher(
+        &self,
+        inner: Arc<dyn Fetcher + Send + Sync>,
+        scheduler: Recipient<RunCompleted>,
+    ) -> CompletionFetcher {
+        CompletionFetcher {
+            inner,
+            task_type: self.task_type.clone(),
+            scheduler,
+            cancelled: self.cancelled.clone(),
+        }
+    }
+}
+
+impl schedule::scheduler::Executor for QueueExecutor {
+    fn execute(&self, task: &schedule::task::Task) {
+        if self.sender.send(self.to_task(task)).is_err() {
+            println!("队列已关闭, 丢弃调度任务: {}", task.id);
+        }
+    }
+
+    // 已经开始的运行无法中止，尚未开始的运行由 CompletionFetcher 在拉取时丢弃
+    fn cancel(&self, task: &schedule::task::Task) {
+        self.cancelled.lock().unwrap().insert(task.id.clone());
+    }
+}
+
+/// 报告触发运行结束的 fetcher，由 `QueueExecutor::completion_fetcher` 创建
+///
+/// 触发的任务被确认，或失败后不再重试（进入死信区）时，向调度器发送 `RunCompleted`，
+/// 调度器据此执行 `Skip`、`Queue` 和 `CancelPrevious` 重叠策略。
+/// 调度器取消（`CancelPrevious`）但还没有开始的运行在拉取时直接确认丢弃。
+pub struct CompletionFetcher {
+    inner: Arc<dyn Fetcher + Send + Sync>,
+    task_type: String,
+    scheduler: Recipient<RunCompleted>,
+    cancelled: CancelledRuns,
+}
+
+impl CompletionFetcher {
+    // 任务对应的触发运行: 触发器 id 在任务数据中，调度任务的 id 是幂等键
+    fn run_of(&self, task: &Task) -> Option<RunCompleted> {
+        if task.task_type != self.task_type {
+            return None;
+        }
+        Some(RunCompleted {
+            trigger_id: task.data["trigger_id"].as_str()?.to_string(),
+            task_id: task.idempotency_key.clone()?,
+        })
+    }
+
+    // 运行结束，通知调度器
+    fn report(&self, task: &Task) {
+        if let Some(run) = self.run_of(task) {
+            self.cancelled.lock().unwrap().remove(&run.task_id);
+            self.scheduler.do_send(run);
+        }
+    }
+}
+
+#[async_trait]
+impl Fetcher for CompletionFetcher {
+    async fn fetch(&self, max: usize) -> Vec<Task> {
+        self.fetch_for(max, None).await
+    }
+
+    async fn fetch_for(&self, max: usize, task_types: Option<&[String]>) -> Vec<Task> {
+        let tasks = self.inner.fetch_for(max, task_types).await;
+
+        // 拉取之后不再 await, 已取消运行的确认交给后台任务，保持拉取可以安全地被取消
+        let mut cancelled = self.cancelled.lock().unwrap();
+        let (skipped, tasks): (Vec<Task>, Vec<Task>) = tasks.into_iter().partition(|task| {
+            self.run_of(task)
+                .is_some_and(|run| cancelled.remove(&run.task_id))
+        });
+        drop(cancelled);
+        if !skipped.is_empty() {
+            let inner = self.inner.clone();
+            tokio::spawn(async move {
+                for task in skipped {
+                    println!("运行已被调度器取消, 丢弃任务: {:?}", task);
+                    inner.ack(&task).await;
+                }
+            });
+        }
+        tasks
+    }
+
+    async fn ack(&self, task: &Task) {
+        self.inner.ack(task).await;
+        self.report(task);
+    }
+
+    async fn nack(&self, task: &Task, requeue_delay: Duration) {
+        self.inner.nack(task, requeue_delay).await;
+    }
+
+    async fn extend_lease(&self, task: &Task) -> bool {
+        self.inner.extend_lease(task).await
+    }
+
+    async fn fail(&self, task: &Task, error: TaskError) {
+        // 与 LocalQueue 的判断一致: 不可重试或用尽投递次数的任务不再重试，运行结束
+        let finished = !error.is_retryable() || task.attempts >= task.max_attempts;
+        self.inner.fail(task, error).await;
+        if finished {
+            self.report(task);
+        }
+    }
+
+    fn poll_strategy(&self) -> PollStrategy {
+        self.inner.poll_strategy()
+    }
+
+    fn notifier(&self) -> Option<Arc<Notify>> {
+        self.inner.notifier()
+    }
+}
@@ -1,9 +1,25 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use tokio::sync::mpsc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
+use tokio::task::{AbortHandle, JoinError, JoinSet};
+use tokio_util::sync::CancellationToken;
 
-use crate::{fetcher::Fetcher, handler2::TaskHandlerExec, task::Task};
+use crate::{
+    fetcher::{Fetcher, PollStrategy},
+    handler2::{TaskError, TaskHandlerExec},
+    registry::{HandlerRegistry, RegistryError},
+    task::Task,
+};
+
+// 默认的停机等待时间
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// 未知类型的任务归还后重新可见的延迟, 留给其他 worker 领取
+const UNKNOWN_REQUEUE_DELAY: Duration = Duration::from_secs(1);
+
+// 默认的续租间隔, 小于 fetcher 默认的可见性超时
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum WorkerStatus {
@@ -11,6 +27,23 @@ pub enum WorkerStatus {
     Busy,
 }
 
+/// 遇到没有注册处理器的任务类型时的处理方式
+///
+/// 除 `Fallback` 外, worker 只向 fetcher 声明已注册的任务类型, 支持按类型拉取的 fetcher
+/// 不会投递未知类型, 只有不支持按类型拉取的 fetcher 才会用到 `Requeue` 和 `DeadLetter`
+#[derive(Clone, Default)]
+pub enum UnknownTaskPolicy {
+    /// 延迟归还给 fetcher, 留给能处理该类型的 worker
+    Requeue,
+
+    /// 作为不可重试的失败交给 fetcher, 由 fetcher 移入死信区
+    #[default]
+    DeadLetter,
+
+    /// 交给兜底处理器处理
+    Fallback(Arc<dyn TaskHandlerExec + Send + Sync>),
+}
+
 pub struct Worker {
     // 当前执行的任务
     pub current_task: Option<Task>,
@@ -24,13 +57,31 @@ pub struct Worker {
     // 任务获取器
     pub fetcher: Arc<dyn Fetcher + Send + Sync>,
 
-    // 允许同时运行的个数
+    // 未知任务类型的处理方式
+    pub unknown_task_policy: UnknownTaskPolicy,
+
+    // 允许同时运行的个数, 0 表示不限制
     pub concurrency: i32,
 
-    // 任务执行的最大次数
+    // 任务执行的最大次数, 0 表示不限制
     pub task_limit: usize,
+
+    // 停机时等待在途任务完成的最长时间, 超时后剩余任务被中止并归还(nack)给 fetcher
+    pub shutdown_timeout: Duration,
+
+    // 任务处理期间向 fetcher 续租的间隔, 应小于 fetcher 的可见性超时
+    pub heartbeat_interval: Duration,
+
+    // 停机信号
+    shutdown: CancellationToken,
 }
 
+// 在途任务: 中止句柄和任务本身, 任务在被中止时需要归还
+type InFlight = HashMap<u64, (AbortHandle, Task)>;
+
+// 在途任务的结束: 序号和处理结果, 外层错误表示任务 panic 或被中止
+type Joined = (u64, Result<Result<(), TaskError>, JoinError>);
+
 impl Worker {
     /// 创建一个新的工人
     pub fn new(fetcher: Arc<dyn Fetcher + Send + Sync>) -> Self {
@@ -39,15 +90,91 @@ impl Worker {
             status: Arc::new(Mutex::new(WorkerStatus::Idle)),
             handlers_map: HashMap::new(),
             fetcher,
+            unknown_task_policy: UnknownTaskPolicy::default(),
             concurrency: 1,
             task_limit: 0,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// 由处理器注册表创建 worker，注册表中的每个任务类型对应一个处理器
+    pub fn from_registry(
+        fetcher: Arc<dyn Fetcher + Send + Sync>,
+        registry: &HandlerRegistry,
+    ) -> Self {
+        let mut worker = Self::new(fetcher);
+        for (task_type, handler) in registry.iter() {
+            worker
+                .handlers_map
+                .insert(task_type.clone(), Arc::new(handler.clone()));
+        }
+        worker
+    }
+
+    /// 添加编译进当前程序的所有登记的处理器(见 `HandlerRegistry::discovered`)，
+    /// 任务类型重复或与已添加的处理器冲突时返回错误
+    pub fn with_discovered_handlers(&mut self) -> Result<(), RegistryError> {
+        let registry = HandlerRegistry::discovered()?;
+        if let Some(task_type) = registry
+            .task_types()
+            .into_iter()
+            .find(|task_type| self.handlers_map.contains_key(task_type))
+        {
+            return Err(RegistryError::Duplicate(task_type));
+        }
+        for (task_type, handler) in registry.iter() {
+            self.handlers_map
+                .insert(task_type.clone(), Arc::new(handler.clone()));
         }
+        Ok(())
     }
 
+    /// 设置本次运行最多处理的任务数, 0 表示不限制
     pub fn with_limit(&mut self, task_limit: usize) {
         self.task_limit = task_limit
     }
 
+    /// 设置停机时等待在途任务完成的最长时间
+    pub fn with_shutdown_timeout(&mut self, shutdown_timeout: Duration) {
+        self.shutdown_timeout = shutdown_timeout
+    }
+
+    /// 设置任务处理期间的续租间隔, 应小于 fetcher 的可见性超时
+    pub fn with_heartbeat_interval(&mut self, heartbeat_interval: Duration) {
+        self.heartbeat_interval = heartbeat_interval
+    }
+
+    /// 设置未知任务类型的处理方式
+    pub fn with_unknown_task_policy(&mut self, policy: UnknownTaskPolicy) {
+        self.unknown_task_policy = policy
+    }
+
+    /// 设置兜底处理器, 没有注册处理器的任务都交给它处理
+    pub fn with_fallback_handler<T: TaskHandlerExec + 'static + Send + Sync>(
+        &mut self,
+        handler: T,
+    ) {
+        self.unknown_task_policy = UnknownTaskPolicy::Fallback(Arc::new(handler))
+    }
+
+    /// 向 fetcher 声明可以处理的任务类型, 即已注册处理器的类型。
+    /// 设置了兜底处理器时能处理任意类型的任务, 返回 `None`
+    pub fn task_types(&self) -> Option<Vec<String>> {
+        if let UnknownTaskPolicy::Fallback(_) = self.unknown_task_policy {
+            return None;
+        }
+        let mut task_types: Vec<String> = self.handlers_map.keys().cloned().collect();
+        task_types.sort();
+        Some(task_types)
+    }
+
+    /// 获取停机句柄, 调用 `cancel()` 后 worker 停止拉取任务并进入排空流程
+    pub fn shutdown_handle(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
     /// 添加任务处理器
     pub fn add_handler<T: TaskHandlerExec + 'static + Send + Sync>(
         &mut self,
@@ -64,61 +191,249 @@ impl Worker {
         }
     }
 
-    /// 异步从 Fetcher 中获取任务，并放入任务队列
+    /// 异步从 Fetcher 中获取一批任务，并放入任务队列
     pub async fn fetch(&self, tx: &mpsc::Sender<Task>) {
-        let fetched_tasks = self.fetcher.fetch().await;
+        let batch_size = self.fetcher.poll_strategy().batch_size;
 
-        if fetched_tasks.is_empty() {
-            println!("暂时没有任务, 继续监听");
-        } else {
-            for task in fetched_tasks {
-                if let Err(_) = tx.send(task).await {
-                    println!("任务发送失败");
-                }
+        let task_types = self.task_types();
+        for task in self
+            .fetcher
+            .fetch_for(batch_size, task_types.as_deref())
+            .await
+        {
+            if tx.send(task).await.is_err() {
+                println!("任务发送失败");
             }
-            println!("任务已加入队列");
         }
     }
 
     /// 主循环，负责交替执行 fetch 和 execute
+    ///
+    /// 拉取节奏由 fetcher 的 `PollStrategy` 决定: 拉取到空结果后按指数退避等待,
+    /// 等待期间 fetcher 的推送通知会立即唤醒 worker。
+    ///
+    /// 只拉取 `task_types` 声明的任务类型, 仍然遇到未注册的类型时按 `unknown_task_policy` 处理。
+    /// 处理成功的任务被确认(ack); 处理器返回错误或 panic 的任务交给 fetcher 的 `fail`,
+    /// 由 fetcher 决定退避重试还是移入死信区。
+    /// 处理期间每隔 `heartbeat_interval` 向 fetcher 续租, 长时间运行的任务不会被重复投递。
+    ///
+    /// 达到 `task_limit` 或收到停机信号后停止拉取任务, 等待在途任务完成后返回。
+    /// 停机时最多等待 `shutdown_timeout`, 超时仍未完成的任务被中止并归还给 fetcher。
     pub async fn run(&mut self) {
-        let (tx, mut rx) = mpsc::channel(100);
-
+        let mut join_set: JoinSet<Joined> = JoinSet::new();
+        let mut in_flight: InFlight = HashMap::new();
+        let mut seq: u64 = 0;
         let mut tasks_processed = 0;
 
-        loop {
-            let fetched_tasks = self.fetcher.fetch().await;
-            for task in fetched_tasks {
-                if tx.send(task).await.is_err() {
-                    println!("任务发送失败");
+        let poll_strategy = self.fetcher.poll_strategy();
+        let notifier = self.fetcher.notifier();
+        let mut idle_interval = poll_strategy.idle_interval;
+        let task_types = self.task_types();
+
+        'fetch: while !self.limit_reached(tasks_processed) {
+            // 并发已满时, 等待任意一个在途任务完成
+            while self.concurrency > 0 && join_set.len() >= self.concurrency as usize {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => break 'fetch,
+                    Some(joined) = join_set.join_next() => self.settle(&mut in_flight, joined).await,
                 }
             }
 
-            // 异步接收任务并执行
-            while let Some(task) = rx.recv().await {
-                let handler_map = self.handlers_map.clone();
-                let status = self.status.clone();
+            let max = self.fetch_size(&poll_strategy, join_set.len(), tasks_processed);
+            let fetched_tasks = tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                tasks = self.fetcher.fetch_for(max, task_types.as_deref()) => tasks,
+            };
 
-                // 根据任务类型执行对应的处理器
-                if let Some(handler) = handler_map.get(&task.task_type) {
-                    let handler = handler.clone();
-                    tokio::spawn(async move {
-                        *status.lock().await = WorkerStatus::Busy;
-                        println!("开始处理任务: {:?}", task);
-                        handler.exec().await;
-                        *status.lock().await = WorkerStatus::Idle;
-                        println!("完成处理任务: {:?}", task);
-                    });
-                } else {
-                    println!("未找到任务类型为 '{}' 的处理器。", task.task_type);
+            if fetched_tasks.is_empty() {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(idle_interval) => {},
+                    _ = Self::notified(&notifier) => {},
                 }
+                idle_interval = poll_strategy.next_interval(idle_interval);
+                continue;
+            }
+            idle_interval = poll_strategy.idle_interval;
 
+            for task in fetched_tasks {
+                // 超出本次运行配额或已在停机的任务, 归还给 fetcher
+                if self.limit_reached(tasks_processed) || self.shutdown.is_cancelled() {
+                    self.fetcher.nack(&task, Duration::ZERO).await;
+                    continue;
+                }
+
+                // 根据任务类型执行对应的处理器
+                let Some(handler) = self.resolve_handler(&task).await else {
+                    continue;
+                };
                 tasks_processed += 1;
-                if tasks_processed >= self.task_limit {
-                    println!("已处理 {} 个任务，退出循环", tasks_processed);
-                    return; // 达到任务限制，退出主循环
+
+                let status = self.status.clone();
+                let fetcher = self.fetcher.clone();
+                let heartbeat_interval = self.heartbeat_interval;
+                let running = task.clone();
+                let handle = tokio::spawn(async move {
+                    *status.lock().await = WorkerStatus::Busy;
+                    println!("开始处理任务: {:?}", running);
+                    let result =
+                        Self::exec_with_heartbeat(handler, fetcher, heartbeat_interval, &running)
+                            .await;
+                    *status.lock().await = WorkerStatus::Idle;
+                    println!("完成处理任务: {:?}", running);
+                    result
+                });
+
+                seq += 1;
+                let id = seq;
+                in_flight.insert(id, (handle.abort_handle(), task));
+                join_set.spawn(async move { (id, handle.await) });
+            }
+        }
+
+        if self.limit_reached(tasks_processed) {
+            println!("已处理 {} 个任务，退出循环", tasks_processed);
+        }
+
+        self.drain(join_set, in_flight).await;
+    }
+
+    // 查找任务类型对应的处理器, 找不到时按未知类型策略处理并返回 None
+    async fn resolve_handler(&self, task: &Task) -> Option<Arc<dyn TaskHandlerExec + Send + Sync>> {
+        if let Some(handler) = self.handlers_map.get(&task.task_type) {
+            return Some(handler.clone());
+        }
+
+        println!("未找到任务类型为 '{}' 的处理器。", task.task_type);
+        match &self.unknown_task_policy {
+            UnknownTaskPolicy::Requeue => {
+                self.fetcher.nack(task, UNKNOWN_REQUEUE_DELAY).await;
+                None
+            }
+            UnknownTaskPolicy::DeadLetter => {
+                let err =
+                    TaskError::Fatal(format!("未找到任务类型为 '{}' 的处理器", task.task_type));
+                self.fetcher.fail(task, err).await;
+                None
+            }
+            UnknownTaskPolicy::Fallback(handler) => Some(handler.clone()),
+        }
+    }
+
+    // 执行处理器, 处理期间每隔 heartbeat_interval 向 fetcher 续租
+    async fn exec_with_heartbeat(
+        handler: Arc<dyn TaskHandlerExec + Send + Sync>,
+        fetcher: Arc<dyn Fetcher + Send + Sync>,
+        heartbeat_interval: Duration,
+        task: &Task,
+    ) -> Result<(), TaskError> {
+        let exec = handler.exec(task);
+        tokio::pin!(exec);
+        let mut heartbeat = tokio::time::interval_at(
+            tokio::time::Instant::now() + heartbeat_interval,
+            heartbeat_interval,
+        );
+        loop {
+            tokio::select! {
+                result = &mut exec => return result,
+                _ = heartbeat.tick() => {
+                    if !fetcher.extend_lease(task).await {
+                        println!("任务租约已失效: {:?}", task);
+                    }
+                }
+            }
+        }
+    }
+
+    // 本次拉取的任务数: 不超过批量大小、空闲并发数和剩余配额
+    fn fetch_size(
+        &self,
+        poll_strategy: &PollStrategy,
+        running: usize,
+        tasks_processed: usize,
+    ) -> usize {
+        let mut max = poll_strategy.batch_size.max(1);
+        if self.concurrency > 0 {
+            max = max.min((self.concurrency as usize).saturating_sub(running).max(1));
+        }
+        if self.task_limit > 0 {
+            max = max.min(self.task_limit - tasks_processed);
+        }
+        max
+    }
+
+    // 等待 fetcher 的推送通知, 没有开启推送模式时永远不会返回
+    async fn notified(notifier: &Option<Arc<Notify>>) {
+        match notifier {
+            Some(notifier) => notifier.notified().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    // 是否达到了本次运行的任务配额
+    fn limit_reached(&self, tasks_processed: usize) -> bool {
+        self.task_limit > 0 && tasks_processed >= self.task_limit
+    }
+
+    // 等待在途任务完成; 一旦收到停机信号, 最多再等待 shutdown_timeout,
+    // 之后中止剩余任务并将它们归还给 fetcher 以便重新投递
+    async fn drain(&self, mut join_set: JoinSet<Joined>, mut in_flight: InFlight) {
+        let shutdown = self.shutdown.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+        let deadline = async move {
+            shutdown.cancelled().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        };
+        tokio::pin!(deadline);
+
+        loop {
+            tokio::select! {
+                joined = join_set.join_next() => match joined {
+                    Some(joined) => self.settle(&mut in_flight, joined).await,
+                    None => return,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+
+        for (abort_handle, _) in in_flight.values() {
+            abort_handle.abort();
+        }
+
+        while let Some(joined) = join_set.join_next().await {
+            match joined {
+                Ok((id, Err(err))) if err.is_cancelled() => {
+                    if let Some((_, task)) = in_flight.remove(&id) {
+                        println!("停机超时, 归还任务: {:?}", task);
+                        self.fetcher.nack(&task, Duration::ZERO).await;
+                    }
+                }
+                joined => self.settle(&mut in_flight, joined).await,
+            }
+        }
+    }
+
+    // 记录一个在途任务的结束: 成功则确认, 失败或异常则交给 fetcher 重试或移入死信区
+    async fn settle(&self, in_flight: &mut InFlight, joined: Result<Joined, JoinError>) {
+        match joined {
+            Ok((id, result)) => {
+                let Some((_, task)) = in_flight.remove(&id) else {
+                    return;
+                };
+                match result {
+                    Ok(Ok(())) => self.fetcher.ack(&task).await,
+                    Ok(Err(err)) => {
+                        println!("任务处理失败: {:?}, 错误: {}", task, err);
+                        self.fetcher.fail(&task, err).await;
+                    }
+                    Err(err) => {
+                        println!("任务执行异常: {:?}, 错误: {}", task, err);
+                        let err = TaskError::Retryable(format!("任务执行异常: {}", err));
+                        self.fetcher.fail(&task, err).await;
+                    }
                 }
             }
+            Err(err) => println!("任务等待异常: {}", err),
         }
     }
 }
@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use autoflow::{
+        fetcher::{LocalQueueFetcher, LocalQueueHandle},
+        handler2::TaskError,
+        registry::{HandlerRegistry, RegistryError},
+        task::Task,
+        worker::Worker,
+    };
+    use serde_json::json;
+
+    static RESIZED: AtomicUsize = AtomicUsize::new(0);
+
+    #[autoflow::task("resize_image")]
+    async fn resize_image(width: u32) -> Result<(), TaskError> {
+        RESIZED.fetch_add(width as usize, Ordering::SeqCst);
+        Ok(())
+    }
+
+    #[autoflow::task("noop")]
+    async fn noop() -> Result<(), TaskError> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_handlers_discovered() {
+        let registry = HandlerRegistry::discovered().unwrap();
+        assert_eq!(registry.task_types(), vec!["noop", "resize_image"]);
+    }
+
+    #[tokio::test]
+    async fn test_worker_with_discovered_handlers() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue_batch(vec![
+                Task::new("resize_image".to_string(), json!({"width": 640})),
+                Task::new("noop".to_string(), json!({})),
+            ])
+            .await;
+
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.with_discovered_handlers().unwrap();
+        worker.with_limit(2);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+        assert!(queue.is_empty().await);
+        assert_eq!(queue.in_flight().await, 0);
+        assert_eq!(RESIZED.load(Ordering::SeqCst), 640);
+    }
+
+    #[test]
+    fn test_discovered_handler_conflicts_with_existing() {
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(LocalQueueHandle::new())));
+        worker.add_handler("noop".to_string(), noop);
+        assert_eq!(
+            worker.with_discovered_handlers(),
+            Err(RegistryError::Duplicate("noop".to_string()))
+        );
+    }
+}
@@ -1,16 +1,28 @@
 #[cfg(test)]
 mod tests {
-    use autoflow::fetcher::{Fetcher, LocalQueue, LocalQueueFetcher, LOCAL_QUEUE_INSTANCE};
+    use std::time::Duration;
+
+    use autoflow::dead_letter::retry_delay;
+    use autoflow::fetcher::{
+        Fetcher, LocalQueue, LocalQueueFetcher, LocalQueueHandle, PollStrategy,
+    };
+    use autoflow::handler2::TaskError;
     use autoflow::task::Task;
+    use chrono::Utc;
     use serde_json::json;
 
+    // 投递会更新任务的投递次数和状态, 只比较任务 id
+    fn ids(tasks: &[Task]) -> Vec<String> {
+        tasks.iter().map(|task| task.id.clone()).collect()
+    }
+
     #[test]
     fn test_enqueue_dequeue() {
         let mut queue = LocalQueue::new();
         assert_eq!(queue.size(), 0);
 
-        let task1 = Task::new(json!({"task": "Task1"}));
-        let task2 = Task::new(json!({"task": "Task2"}));
+        let task1 = Task::new("test".to_string(), json!({"task": "Task1"}));
+        let task2 = Task::new("test".to_string(), json!({"task": "Task2"}));
 
         queue.enqueue(task1.clone());
         assert_eq!(queue.size(), 1);
@@ -30,27 +42,428 @@ mod tests {
         assert_eq!(dequeued3, None);
     }
 
-    #[test]
-    fn test_singleton_enqueue_dequeue() {
-        let task1 = Task::new(json!({"task": "SingletonTask1"}));
-        let task2 = Task::new(json!({"task": "SingletonTask2"}));
+    #[tokio::test]
+    async fn test_handle_enqueue_dequeue() {
+        let task1 = Task::new("test".to_string(), json!({"task": "HandleTask1"}));
+        let task2 = Task::new("test".to_string(), json!({"task": "HandleTask2"}));
 
         // 入队
-        {
-            let mut queue = LOCAL_QUEUE_INSTANCE.lock().unwrap();
-            queue.enqueue(task1.clone());
-            queue.enqueue(task2.clone());
-        }
+        let queue = LocalQueueHandle::new();
+        queue.enqueue(task1.clone()).await;
+        queue.enqueue(task2.clone()).await;
+        assert_eq!(queue.len().await, 2);
 
         // 出队
-        let fetcher = LocalQueueFetcher;
-        let fetched_tasks = fetcher.fetch();
-        assert_eq!(fetched_tasks, vec![task1]);
+        let fetcher = LocalQueueFetcher::new(queue.clone());
+        let fetched_tasks = fetcher.fetch(1).await;
+        assert_eq!(ids(&fetched_tasks), vec![task1.id]);
 
-        let fetched_tasks = fetcher.fetch();
-        assert_eq!(fetched_tasks, vec![task2]);
+        let fetched_tasks = fetcher.fetch(1).await;
+        assert_eq!(ids(&fetched_tasks), vec![task2.id]);
 
-        let fetched_tasks = fetcher.fetch();
+        let fetched_tasks = fetcher.fetch(1).await;
         assert!(fetched_tasks.is_empty());
+        assert_eq!(queue.in_flight().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_handles_are_independent() {
+        let queue_a = LocalQueueHandle::new();
+        let queue_b = LocalQueueHandle::new();
+
+        let tasks: Vec<Task> = (0..3)
+            .map(|i| Task::new("test".to_string(), json!({ "index": i })))
+            .collect();
+        queue_a.enqueue_batch(tasks.clone()).await;
+
+        // 不同句柄之间互不影响
+        assert_eq!(queue_a.len().await, 3);
+        assert!(queue_b.is_empty().await);
+
+        let fetcher_b = LocalQueueFetcher::new(queue_b);
+        assert!(fetcher_b.fetch(10).await.is_empty());
+
+        let fetcher_a = LocalQueueFetcher::new(queue_a);
+        assert_eq!(ids(&fetcher_a.fetch(10).await), ids(&tasks));
+    }
+
+    #[test]
+    fn test_poll_strategy_backoff() {
+        let strategy = PollStrategy {
+            idle_interval: Duration::from_millis(100),
+            max_interval: Duration::from_millis(500),
+            backoff_factor: 2,
+            batch_size: 10,
+        };
+
+        // 连续空结果时等待间隔翻倍, 直到上限
+        let mut interval = strategy.idle_interval;
+        let mut intervals = vec![];
+        for _ in 0..4 {
+            interval = strategy.next_interval(interval);
+            intervals.push(interval.as_millis());
+        }
+        assert_eq!(intervals, vec![200, 400, 500, 500]);
+    }
+
+    #[test]
+    fn test_unacked_task_redelivered_after_lease_expires() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Lease"}));
+        queue.enqueue(task.clone());
+
+        let leased = queue.lease(10, Duration::from_millis(50));
+        assert_eq!(ids(&leased), vec![task.id.clone()]);
+        assert_eq!(leased[0].attempts, 1);
+
+        // 租约未到期前不可见
+        assert!(queue.lease(10, Duration::from_millis(50)).is_empty());
+        assert_eq!(queue.in_flight(), 1);
+
+        // 租约到期后重新投递
+        std::thread::sleep(Duration::from_millis(60));
+        let leased = queue.lease(10, Duration::from_millis(50));
+        assert_eq!(ids(&leased), vec![task.id]);
+        assert_eq!(leased[0].attempts, 2);
+    }
+
+    #[test]
+    fn test_deserialize_task_without_new_fields() {
+        // 只有 task_type、data 和 status 的旧格式任务
+        let task: Task = serde_json::from_value(json!({
+            "task_type": "test",
+            "data": {"task": "Old"},
+            "status": "Queued",
+        }))
+        .unwrap();
+
+        assert!(!task.id.is_empty());
+        assert_eq!(task.priority, 0);
+        assert_eq!(task.attempts, 0);
+        assert_eq!(
+            task.max_attempts,
+            Task::new("test".to_string(), json!({})).max_attempts
+        );
+        assert!(task.idempotency_key.is_none());
+        assert!(task.is_visible(Utc::now()));
+    }
+
+    #[test]
+    fn test_ack_removes_task() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Ack"}));
+        queue.enqueue(task.clone());
+
+        let leased = queue.lease(1, Duration::from_millis(10));
+        assert!(queue.ack(&leased[0]));
+        assert!(!queue.ack(&leased[0]));
+        assert_eq!(queue.in_flight(), 0);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(queue.lease(10, Duration::from_millis(10)).is_empty());
+    }
+
+    #[test]
+    fn test_stale_ack_after_redelivery_is_rejected() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Slow"}));
+        queue.enqueue(task.clone());
+
+        // 第一次投递的租约到期后任务被重新投递
+        let first = queue.lease(1, Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(20));
+        let second = queue.lease(1, Duration::from_secs(30));
+        assert_eq!(ids(&second), vec![task.id.clone()]);
+
+        // 第一次投递迟到的确认不影响新的租约
+        assert!(!queue.ack(&first[0]));
+        assert!(!queue.nack(&first[0], Duration::ZERO));
+        assert!(!queue.extend_lease(&first[0], Duration::from_secs(30)));
+        assert_eq!(queue.in_flight(), 1);
+
+        assert!(queue.ack(&second[0]));
+        assert_eq!(queue.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_extend_lease_delays_redelivery() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Long"}));
+        queue.enqueue(task.clone());
+
+        let leased = queue.lease(1, Duration::from_millis(30));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(queue.extend_lease(&leased[0], Duration::from_secs(30)));
+
+        // 超过原来的租约时长后仍然不会重新投递
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(queue.lease(10, Duration::from_secs(30)).is_empty());
+        assert!(queue.ack(&leased[0]));
+    }
+
+    #[test]
+    fn test_nack_requeues_after_delay() {
+        let mut queue = LocalQueue::new();
+        let task1 = Task::new("test".to_string(), json!({"task": "Nack1"}));
+        let task2 = Task::new("test".to_string(), json!({"task": "Nack2"}));
+        queue.enqueue(task1.clone());
+        queue.enqueue(task2.clone());
+
+        let leased = queue.lease(2, Duration::from_secs(30));
+        assert_eq!(ids(&leased), vec![task1.id.clone(), task2.id.clone()]);
+
+        // 无延迟的 nack 立即重新可见
+        assert!(queue.nack(&leased[0], Duration::ZERO));
+        // 有延迟的 nack 在延迟之后才重新可见
+        assert!(queue.nack(&leased[1], Duration::from_millis(50)));
+
+        assert_eq!(
+            ids(&queue.lease(10, Duration::from_secs(30))),
+            vec![task1.id]
+        );
+        assert!(queue.lease(10, Duration::from_secs(30)).is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(
+            ids(&queue.lease(10, Duration::from_secs(30))),
+            vec![task2.id]
+        );
+    }
+
+    #[test]
+    fn test_higher_priority_delivered_first() {
+        let mut queue = LocalQueue::new();
+        let low = Task::new("test".to_string(), json!({"task": "Low"}));
+        let normal = Task::new("test".to_string(), json!({"task": "Normal"}));
+        let urgent = Task::new("test".to_string(), json!({"task": "Urgent"})).with_priority(10);
+        let backlog = Task::new("test".to_string(), json!({"task": "Backlog"})).with_priority(-1);
+        queue.enqueue(backlog.clone());
+        queue.enqueue(low.clone());
+        queue.enqueue(normal.clone());
+        queue.enqueue(urgent.clone());
+
+        // 优先级高的先投递, 同优先级按入队先后
+        assert_eq!(
+            ids(&queue.lease(10, Duration::from_secs(30))),
+            vec![urgent.id, low.id, normal.id, backlog.id]
+        );
+    }
+
+    #[test]
+    fn test_delayed_task_invisible_until_run_after() {
+        let mut queue = LocalQueue::new();
+        let delayed = Task::new("test".to_string(), json!({"task": "Delayed"}))
+            .with_delay(Duration::from_millis(50))
+            .with_priority(10);
+        let scheduled = Task::new("test".to_string(), json!({"task": "Scheduled"}))
+            .with_run_after(Utc::now() - chrono::Duration::seconds(1));
+        queue.enqueue(delayed.clone());
+        queue.enqueue(scheduled.clone());
+        assert_eq!(queue.size(), 2);
+
+        // 延迟任务即使优先级更高, 在 run_after 之前也不可见
+        assert_eq!(
+            ids(&queue.lease(10, Duration::from_secs(30))),
+            vec![scheduled.id]
+        );
+        assert!(queue.lease(10, Duration::from_secs(30)).is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(
+            ids(&queue.lease(10, Duration::from_secs(30))),
+            vec![delayed.id]
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        let base = Duration::from_secs(1);
+        let delays: Vec<u64> = [1, 2, 3, 4, 20]
+            .into_iter()
+            .map(|attempts| retry_delay(base, attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 300]);
+    }
+
+    #[test]
+    fn test_failed_task_retried_then_dead_lettered() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Poison"})).with_max_attempts(2);
+        queue.enqueue(task.clone());
+
+        let leased = queue.lease(1, Duration::from_secs(30));
+        let failure = queue
+            .fail(
+                &leased[0],
+                TaskError::Retryable("timeout".to_string()),
+                Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(failure.record.attempt, 1);
+        assert!(failure.retry_at.is_some());
+
+        // 第二次失败时用尽投递次数, 进入死信区
+        let leased = queue.lease(1, Duration::from_secs(30));
+        assert_eq!(ids(&leased), vec![task.id.clone()]);
+        let failure = queue
+            .fail(
+                &leased[0],
+                TaskError::Retryable("timeout again".to_string()),
+                Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(failure.retry_at, None);
+        assert_eq!(queue.size(), 0);
+
+        let dead = queue.dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task.data, task.data);
+        assert_eq!(dead[0].history.len(), 2);
+        assert_eq!(
+            dead[0].last_error,
+            TaskError::Retryable("timeout again".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fatal_error_dead_letters_immediately() {
+        let mut queue = LocalQueue::new();
+        let task = Task::new("test".to_string(), json!({"task": "Fatal"}));
+        queue.enqueue(task.clone());
+
+        let leased = queue.lease(1, Duration::from_secs(30));
+        let failure = queue
+            .fail(
+                &leased[0],
+                TaskError::Fatal("invalid".to_string()),
+                Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(failure.retry_at, None);
+        assert_eq!(queue.dead_letters().len(), 1);
+
+        // 不在租约中的任务不能再次失败
+        assert!(queue
+            .fail(
+                &leased[0],
+                TaskError::Fatal("invalid".to_string()),
+                Duration::ZERO
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_requeue_and_purge_dead_letters() {
+        let mut queue = LocalQueue::new();
+        let tasks: Vec<Task> = (0..3)
+            .map(|i| Task::new("test".to_string(), json!({ "index": i })))
+            .collect();
+        for task in &tasks {
+            queue.enqueue(task.clone());
+        }
+        for task in queue.lease(3, Duration::from_secs(30)) {
+            queue.fail(
+                &task,
+                TaskError::Fatal("invalid".to_string()),
+                Duration::ZERO,
+            );
+        }
+        assert_eq!(queue.dead_letters().len(), 3);
+
+        // 重新入队的任务投递次数清零
+        let requeued = queue.requeue_dead_letter(&tasks[0].id).unwrap();
+        assert_eq!(requeued.attempts, 0);
+        assert!(queue.requeue_dead_letter(&tasks[0].id).is_none());
+        let leased = queue.lease(10, Duration::from_secs(30));
+        assert_eq!(ids(&leased), vec![tasks[0].id.clone()]);
+        assert_eq!(leased[0].attempts, 1);
+
+        assert!(queue.purge_dead_letter(&tasks[1].id));
+        assert!(!queue.purge_dead_letter(&tasks[1].id));
+        assert_eq!(queue.purge_dead_letters(), 1);
+        assert!(queue.dead_letters().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_idempotency_key_rejected() {
+        let mut queue = LocalQueue::new();
+        let task =
+            Task::new("test".to_string(), json!({"task": "Order"})).with_idempotency_key("order-1");
+        let retry =
+            Task::new("test".to_string(), json!({"task": "Order"})).with_idempotency_key("order-1");
+        let other =
+            Task::new("test".to_string(), json!({"task": "Order"})).with_idempotency_key("order-2");
+
+        assert!(queue.enqueue(task.clone()));
+        assert!(!queue.enqueue(retry));
+        assert!(queue.enqueue(other));
+        assert_eq!(queue.size(), 2);
+
+        // 任务处理完成后, 去重窗口内仍然拒绝相同的键
+        let leased = queue.lease(1, Duration::from_secs(30));
+        assert_eq!(ids(&leased), vec![task.id.clone()]);
+        queue.ack(&leased[0]);
+        assert!(queue.is_duplicate(&task));
+
+        // 没有幂等键的任务不去重
+        let plain = Task::new("test".to_string(), json!({}));
+        assert!(queue.enqueue(plain.clone()));
+        assert!(queue.enqueue(plain));
+    }
+
+    #[test]
+    fn test_idempotency_key_accepted_after_window() {
+        let mut queue = LocalQueue::new();
+        queue.set_dedup_window(Duration::from_millis(50));
+        let task = Task::new("test".to_string(), json!({})).with_idempotency_key("trigger-1");
+
+        assert!(queue.enqueue(task.clone()));
+        assert!(!queue.enqueue(task.clone()));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(queue.enqueue(task));
+        assert_eq!(queue.size(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_handle_batch_skips_duplicates() {
+        let queue = LocalQueueHandle::with_dedup_window(Duration::from_secs(60));
+        let tasks = vec![
+            Task::new("test".to_string(), json!({})).with_idempotency_key("a"),
+            Task::new("test".to_string(), json!({})).with_idempotency_key("a"),
+            Task::new("test".to_string(), json!({})).with_idempotency_key("b"),
+        ];
+
+        assert_eq!(queue.enqueue_batch(tasks).await, 2);
+        assert!(
+            !queue
+                .enqueue(Task::new("test".to_string(), json!({})).with_idempotency_key("b"))
+                .await
+        );
+        assert_eq!(queue.len().await, 2);
+    }
+
+    #[test]
+    fn test_lease_for_filters_task_types() {
+        let mut queue = LocalQueue::new();
+        let email = Task::new("email".to_string(), json!({}));
+        let report = Task::new("report".to_string(), json!({})).with_priority(10);
+        let sms = Task::new("sms".to_string(), json!({}));
+        queue.enqueue(email.clone());
+        queue.enqueue(report.clone());
+        queue.enqueue(sms.clone());
+
+        let types = vec!["email".to_string(), "sms".to_string()];
+        assert_eq!(
+            ids(&queue.lease_for(10, Duration::from_secs(30), Some(&types))),
+            vec![email.id, sms.id]
+        );
+
+        // 跳过的任务保持原有顺序, 仍可被其他 worker 领取
+        assert_eq!(queue.size(), 1);
+        assert_eq!(
+            ids(&queue.lease_for(10, Duration::from_secs(30), None)),
+            vec![report.id]
+        );
     }
 }
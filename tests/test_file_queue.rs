@@ -0,0 +1,300 @@
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use autoflow::{
+        dead_letter::{DeadLetter, DeadLetterQueue},
+        fetcher::Fetcher,
+        file_queue::FileQueue,
+        handler2::TaskError,
+        task::Task,
+    };
+    use serde_json::json;
+
+    // 投递会更新任务的投递次数和状态, 只比较任务 id
+    fn ids(tasks: &[Task]) -> Vec<String> {
+        tasks.iter().map(|task| task.id.clone()).collect()
+    }
+
+    fn dead_ids(dead: &[DeadLetter]) -> Vec<String> {
+        dead.iter().map(|dead| dead.task.id.clone()).collect()
+    }
+
+    // 每个测试使用独立的日志文件
+    fn log_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("autoflow-{}-{}.log", name, nanoid::nanoid!(8)));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_tasks_survive_restart() {
+        let path = log_path("restart");
+        let task1 = Task::new("test".to_string(), json!({"task": "Task1"}));
+        let task2 = Task::new("test".to_string(), json!({"task": "Task2"}));
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(task1.clone()).await.unwrap();
+            queue.enqueue(task2.clone()).await.unwrap();
+            assert_eq!(queue.len().await, 2);
+        }
+
+        // 重新打开后任务仍然存在，且保持入队顺序
+        let queue = FileQueue::open(&path).unwrap();
+        assert_eq!(queue.len().await, 2);
+        assert_eq!(ids(&queue.fetch(10).await), vec![task1.id, task2.id]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_log_written_before_task_fields() {
+        let path = log_path("old-format");
+        let line = json!({
+            "op": "enqueue",
+            "task": {"id": "old", "task_type": "test", "data": {"task": "Old"}, "status": "Queued"},
+        });
+        std::fs::write(&path, format!("{}\n", line)).unwrap();
+
+        // 旧格式的日志记录不能被当作半行数据丢弃
+        let queue = FileQueue::open(&path).unwrap();
+        assert_eq!(ids(&queue.fetch(10).await), vec!["old".to_string()]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acked_tasks_are_not_redelivered_after_restart() {
+        let path = log_path("ack");
+        let acked = Task::new("test".to_string(), json!({"task": "Acked"}));
+        let unacked = Task::new("test".to_string(), json!({"task": "Unacked"}));
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(acked.clone()).await.unwrap();
+            queue.enqueue(unacked.clone()).await.unwrap();
+
+            let fetched = queue.fetch(10).await;
+            assert_eq!(ids(&fetched), vec![acked.id.clone(), unacked.id.clone()]);
+            queue.ack(&fetched[0]).await;
+            // unacked 在进程崩溃前没有被确认
+        }
+
+        let queue = FileQueue::open(&path).unwrap();
+        assert_eq!(ids(&queue.fetch(10).await), vec![unacked.id]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_fetch_returns_leased_tasks() {
+        let path = log_path("cancel");
+        let task = Task::new("test".to_string(), json!({"task": "Cancelled"}));
+        let queue = FileQueue::open(&path).unwrap();
+        queue.enqueue(task.clone()).await.unwrap();
+
+        // 拉取在等待日志落盘时被取消, 和 worker 停机时 select! 取消拉取一样
+        let cancelled = tokio::select! {
+            biased;
+            _ = queue.fetch(1) => false,
+            _ = std::future::ready(()) => true,
+        };
+        assert!(
+            cancelled,
+            "The fetch completed before it could be cancelled"
+        );
+
+        // 任务立即回到队列, 不必等到可见性超时
+        assert_eq!(queue.len().await, 1);
+        assert_eq!(ids(&queue.fetch(1).await), vec![task.id]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_task_moves_to_dead_letters_after_max_attempts() {
+        let path = log_path("dead");
+        let task = Task::new("test".to_string(), json!({"task": "Poison"})).with_max_attempts(2);
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(task.clone()).await.unwrap();
+
+            for _ in 0..2 {
+                let fetched = queue.fetch(1).await;
+                assert_eq!(ids(&fetched), vec![task.id.clone()]);
+                queue.nack(&fetched[0], Duration::ZERO).await;
+            }
+
+            // 第三次投递时超过最大次数，进入死信区
+            assert!(queue.fetch(1).await.is_empty());
+            assert_eq!(dead_ids(&queue.dead_letters().await), vec![task.id.clone()]);
+            assert!(queue.is_empty().await);
+        }
+
+        // 死信区同样持久化
+        let queue = FileQueue::open(&path).unwrap();
+        assert!(queue.fetch(1).await.is_empty());
+        assert_eq!(dead_ids(&queue.dead_letters().await), vec![task.id]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_attempts_survive_restart() {
+        let path = log_path("attempts");
+        let task = Task::new("test".to_string(), json!({"task": "Retry"})).with_max_attempts(2);
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(task.clone()).await.unwrap();
+            assert_eq!(ids(&queue.fetch(1).await), vec![task.id.clone()]);
+        }
+
+        // 重启前已投递过一次，重启后只剩一次投递机会
+        let queue = FileQueue::open(&path).unwrap();
+        let fetched = queue.fetch(1).await;
+        assert_eq!(ids(&fetched), vec![task.id.clone()]);
+        assert_eq!(fetched[0].attempts, 2);
+        queue.nack(&fetched[0], Duration::ZERO).await;
+        assert!(queue.fetch(1).await.is_empty());
+        assert_eq!(dead_ids(&queue.dead_letters().await), vec![task.id]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requeue_delay_survives_restart() {
+        let path = log_path("requeue");
+        let task = Task::new("test".to_string(), json!({"task": "Later"}));
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(task.clone()).await.unwrap();
+            let fetched = queue.fetch(1).await;
+            assert_eq!(ids(&fetched), vec![task.id.clone()]);
+            queue.nack(&fetched[0], Duration::from_secs(60)).await;
+        }
+
+        // 重启后任务仍在延迟中，不会提前投递
+        let queue = FileQueue::open(&path).unwrap();
+        assert_eq!(queue.len().await, 1);
+        assert!(queue.fetch(1).await.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failure_history_survives_restart() {
+        let path = log_path("history");
+        let retried = Task::new("test".to_string(), json!({"task": "Retried"}));
+        let fatal = Task::new("test".to_string(), json!({"task": "Fatal"}));
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(retried.clone()).await.unwrap();
+            queue.enqueue(fatal.clone()).await.unwrap();
+            let fetched = queue.fetch(2).await;
+            assert_eq!(ids(&fetched), vec![retried.id.clone(), fatal.id.clone()]);
+            queue
+                .fail(&fetched[0], TaskError::Retryable("timeout".to_string()))
+                .await;
+            queue
+                .fail(&fetched[1], TaskError::Fatal("invalid".to_string()))
+                .await;
+        }
+
+        let queue = FileQueue::open(&path).unwrap();
+        // 可重试的任务仍在退避中
+        assert_eq!(queue.len().await, 1);
+        assert!(queue.fetch(10).await.is_empty());
+
+        let dead = queue.dead_letters().await;
+        assert_eq!(dead_ids(&dead), vec![fatal.id.clone()]);
+        assert_eq!(dead[0].task.data, fatal.data);
+        assert_eq!(dead[0].last_error, TaskError::Fatal("invalid".to_string()));
+        assert_eq!(dead[0].history.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requeue_and_purge_survive_restart() {
+        let path = log_path("purge");
+        let requeued = Task::new("test".to_string(), json!({"task": "Requeued"}));
+        let purged = Task::new("test".to_string(), json!({"task": "Purged"}));
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.enqueue(requeued.clone()).await.unwrap();
+            queue.enqueue(purged.clone()).await.unwrap();
+            for task in queue.fetch(2).await {
+                queue
+                    .fail(&task, TaskError::Fatal("invalid".to_string()))
+                    .await;
+            }
+            assert_eq!(queue.dead_letters().await.len(), 2);
+
+            assert!(queue.requeue_dead_letter(&requeued.id).await);
+            assert!(queue.purge_dead_letter(&purged.id).await);
+        }
+
+        // 重新入队的任务投递次数清零，删除的死信不再出现
+        let queue = FileQueue::open(&path).unwrap();
+        assert!(queue.dead_letters().await.is_empty());
+        let fetched = queue.fetch(10).await;
+        assert_eq!(ids(&fetched), vec![requeued.id]);
+        assert_eq!(fetched[0].attempts, 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_keys_survive_restart() {
+        let path = log_path("dedup");
+        let task =
+            Task::new("test".to_string(), json!({"task": "Once"})).with_idempotency_key("run-1");
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            assert!(queue.enqueue(task.clone()).await.unwrap());
+            assert!(!queue.enqueue(task.clone()).await.unwrap());
+
+            // 任务处理完成后删除，幂等键仍然保留
+            let fetched = queue.fetch(1).await;
+            assert_eq!(fetched.len(), 1);
+            queue.ack(&fetched[0]).await;
+        }
+
+        // 重启并压缩日志后，相同的键仍在去重窗口内
+        let queue = FileQueue::open(&path).unwrap();
+        assert!(queue.is_empty().await);
+        let retry =
+            Task::new("test".to_string(), json!({"task": "Once"})).with_idempotency_key("run-1");
+        assert!(!queue.enqueue(retry).await.unwrap());
+        assert!(queue.fetch(1).await.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_accepted_after_window() {
+        let path = log_path("window");
+        let task = Task::new("test".to_string(), json!({})).with_idempotency_key("run-2");
+
+        {
+            let queue = FileQueue::open(&path).unwrap();
+            queue.set_dedup_window(Duration::from_millis(50)).await;
+            assert!(queue.enqueue(task.clone()).await.unwrap());
+        }
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let queue = FileQueue::open(&path).unwrap();
+        assert!(queue.enqueue(task).await.unwrap());
+        assert_eq!(queue.len().await, 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use autoflow::{
+        fetcher::{LocalQueueFetcher, LocalQueueHandle},
+        handler2::{TaskError, TaskHandler},
+        registry::{HandlerRegistry, RegistryError},
+        task::Task,
+        worker::Worker,
+    };
+    use serde_json::json;
+
+    struct EmailHandler;
+
+    #[async_trait]
+    impl TaskHandler for EmailHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            "email"
+        }
+    }
+
+    // 与 EmailHandler 使用相同任务类型的处理器
+    struct AnotherEmailHandler;
+
+    #[async_trait]
+    impl TaskHandler for AnotherEmailHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            "email"
+        }
+    }
+
+    #[test]
+    fn test_builtin_handlers_have_distinct_keys() {
+        let registry = HandlerRegistry::builtin().unwrap();
+        assert_eq!(registry.task_types(), vec!["custom", "end", "start"]);
+        assert_eq!(registry.get("start").unwrap().for_task(), "start");
+        assert!(registry.get("unknown").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_task_type_rejected() {
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(EmailHandler)).unwrap();
+        assert_eq!(
+            registry.register(Arc::new(AnotherEmailHandler)),
+            Err(RegistryError::Duplicate("email".to_string()))
+        );
+        assert_eq!(registry.len(), 1);
+
+        let result = HandlerRegistry::from_handlers([
+            Arc::new(EmailHandler) as _,
+            Arc::new(AnotherEmailHandler) as _,
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_worker_from_registry() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue_batch(vec![
+                Task::new("start".to_string(), json!({})),
+                Task::new("email".to_string(), json!({})),
+                Task::new("end".to_string(), json!({})),
+            ])
+            .await;
+
+        let mut registry = HandlerRegistry::builtin().unwrap();
+        registry.register(Arc::new(EmailHandler)).unwrap();
+
+        let mut worker =
+            Worker::from_registry(Arc::new(LocalQueueFetcher::new(queue.clone())), &registry);
+        worker.with_limit(3);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+        assert!(queue.is_empty().await);
+        assert_eq!(queue.in_flight().await, 0);
+    }
+}
@@ -0,0 +1,198 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use actix::{Actor, AsyncContext};
+    use async_trait::async_trait;
+    use autoflow::{
+        fetcher::{Fetcher, LocalQueueFetcher, LocalQueueHandle},
+        handler2::{TaskError, TaskHandler},
+        task::Task,
+        trigger_executor::{QueueExecutor, WORKFLOW_TASK_TYPE},
+        worker::Worker,
+    };
+    use chrono::{DateTime, Utc};
+    use schedule::{
+        clock::ManualClock,
+        scheduler::{AddTasks, AddTrigger, Executor, Scheduler},
+        trigger::{OverlapPolicy, Trigger},
+    };
+    use tokio::sync::Semaphore;
+
+    // 记录收到的工作流运行
+    struct WorkflowHandler(Arc<Mutex<Vec<(String, String)>>>);
+
+    #[async_trait]
+    impl TaskHandler for WorkflowHandler {
+        async fn handle(&self, task: &Task) -> Result<(), TaskError> {
+            self.0.lock().unwrap().push((
+                task.data["workflow_id"].as_str().unwrap().to_string(),
+                task.data["trigger_id"].as_str().unwrap().to_string(),
+            ));
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            WORKFLOW_TASK_TYPE
+        }
+    }
+
+    // 记录运行的计划时间，每次运行要等到 gate 放行才结束
+    struct GatedHandler {
+        runs: Arc<Mutex<Vec<String>>>,
+        gate: Arc<Semaphore>,
+    }
+
+    #[async_trait]
+    impl TaskHandler for GatedHandler {
+        async fn handle(&self, task: &Task) -> Result<(), TaskError> {
+            self.runs
+                .lock()
+                .unwrap()
+                .push(task.data["scheduled_at"].as_str().unwrap().to_string());
+            self.gate.acquire().await.unwrap().forget();
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            WORKFLOW_TASK_TYPE
+        }
+    }
+
+    async fn wait_for_runs(runs: &Arc<Mutex<Vec<String>>>, n: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while runs.lock().unwrap().len() < n {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The trigger did not run");
+    }
+
+    #[actix_rt::test]
+    async fn test_skip_overlap_policy_end_to_end() {
+        let start: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let clock = ManualClock::new(start);
+        let queue = LocalQueueHandle::new();
+
+        let inner: Arc<dyn Fetcher + Send + Sync> = Arc::new(LocalQueueFetcher::new(queue.clone()));
+        let mut fetcher = None;
+        let scheduler = Scheduler::create(|ctx| {
+            let executor = QueueExecutor::new(queue.clone());
+            fetcher = Some(executor.completion_fetcher(inner, ctx.address().recipient()));
+            Scheduler::new(executor)
+                .with_clock(clock.clone())
+                .with_tick_interval(Duration::from_millis(10))
+        });
+        scheduler
+            .send(AddTrigger(
+                Trigger::interval(
+                    "trigger1",
+                    chrono::Duration::seconds(1),
+                    start + chrono::Duration::seconds(1),
+                    "workflow1",
+                )
+                .with_overlap_policy(OverlapPolicy::Skip),
+            ))
+            .await
+            .unwrap();
+
+        let runs = Arc::new(Mutex::new(vec![]));
+        let gate = Arc::new(Semaphore::new(0));
+        let mut worker = Worker::new(Arc::new(fetcher.unwrap()));
+        worker.add_handler(
+            WORKFLOW_TASK_TYPE.to_string(),
+            GatedHandler {
+                runs: runs.clone(),
+                gate: gate.clone(),
+            },
+        );
+        worker.with_limit(2);
+        let worker = actix_rt::spawn(async move { worker.run().await });
+
+        // 第一次运行开始
+        clock.advance(chrono::Duration::seconds(1));
+        wait_for_runs(&runs, 1).await;
+
+        // 第一次运行尚未结束，第二次触发被跳过
+        clock.advance(chrono::Duration::seconds(1));
+        actix::clock::sleep(Duration::from_millis(100)).await;
+        assert_eq!(runs.lock().unwrap().len(), 1);
+
+        // 第一次运行结束后，worker 报告完成，第三次触发正常运行
+        gate.add_permits(1);
+        actix::clock::sleep(Duration::from_millis(100)).await;
+        clock.advance(chrono::Duration::seconds(1));
+        wait_for_runs(&runs, 2).await;
+        gate.add_permits(1);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker).await;
+        assert!(result.is_ok(), "The worker run timed out");
+        assert_eq!(
+            *runs.lock().unwrap(),
+            vec!["2024-01-01T00:00:01Z", "2024-01-01T00:00:03Z"]
+        );
+        assert!(queue.is_empty().await);
+    }
+
+    #[actix_rt::test]
+    async fn test_fired_trigger_runs_workflow() {
+        let queue = LocalQueueHandle::new();
+        let scheduler = Scheduler::new(QueueExecutor::new(queue.clone())).start();
+
+        let now = Utc::now();
+        scheduler.do_send(AddTasks(vec![schedule::task::Task::new(
+            "trigger1-1",
+            now,
+            "workflow1",
+            "trigger1",
+        )]));
+
+        // 等待调度器触发并入队
+        actix::clock::sleep(Duration::from_millis(500)).await;
+        assert_eq!(queue.len().await, 1);
+
+        let runs = Arc::new(Mutex::new(vec![]));
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.add_handler(
+            WORKFLOW_TASK_TYPE.to_string(),
+            WorkflowHandler(runs.clone()),
+        );
+        worker.with_limit(1);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+        assert_eq!(
+            *runs.lock().unwrap(),
+            vec![("workflow1".to_string(), "trigger1".to_string())]
+        );
+        assert!(queue.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_payload_and_idempotency() {
+        let queue = LocalQueueHandle::new();
+        let executor = QueueExecutor::new(queue.clone()).with_task_type("cron");
+
+        let run_at = Utc::now();
+        let scheduled = schedule::task::Task::new("trigger1-1", run_at, "workflow1", "trigger1");
+        let task = executor.to_task(&scheduled);
+        assert_eq!(task.task_type, "cron");
+        assert_eq!(task.data["workflow_id"], "workflow1");
+        assert_eq!(task.data["trigger_id"], "trigger1");
+        assert_eq!(
+            task.data["scheduled_at"],
+            serde_json::to_value(run_at).unwrap()
+        );
+        assert_eq!(task.idempotency_key.as_deref(), Some("trigger1-1"));
+
+        // 同一次触发重复投递只入队一次
+        executor.execute(&scheduled);
+        executor.execute(&scheduled);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queue.len().await, 1);
+    }
+}
@@ -1,27 +1,33 @@
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::Arc,
+        time::Duration,
+    };
 
+    use async_trait::async_trait;
     use autoflow::{
-        fetcher::LocalQueueFetcher,
-        handler2::TaskHandler,
-        task::{Task, TaskStatus},
-        worker::Worker,
+        dead_letter::DeadLetterQueue,
+        fetcher::{Fetcher, LocalQueueFetcher, LocalQueueHandle, PollStrategy},
+        handler2::{TaskError, TaskHandler},
+        task::Task,
+        worker::{UnknownTaskPolicy, Worker},
     };
     use serde_json::json;
-
-    use crate::tests;
+    use tokio::sync::{Mutex, Notify};
 
     struct MockTaskHandler;
 
-    #[async_trait::async_trait]
+    #[async_trait]
     impl TaskHandler for MockTaskHandler {
         async fn before(&self) {
             println!("Mock: before task");
         }
 
-        async fn handle(&self) {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
             println!("Mock: handling task");
+            Ok(())
         }
 
         async fn after(&self) {
@@ -33,69 +39,533 @@ mod tests {
         }
     }
 
+    // 执行很慢的处理器, 用于模拟停机时仍在运行的任务
+    struct SlowTaskHandler;
+
+    #[async_trait]
+    impl TaskHandler for SlowTaskHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            "slow"
+        }
+    }
+
+    // 处理时间超过可见性超时的处理器, 用于验证续租
+    struct DelayTaskHandler(Duration);
+
+    #[async_trait]
+    impl TaskHandler for DelayTaskHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            tokio::time::sleep(self.0).await;
+            Ok(())
+        }
+
+        fn for_task(&self) -> &'static str {
+            "delay"
+        }
+    }
+
+    // 总是失败的处理器, 用于模拟毒任务
+    struct FailingTaskHandler(TaskError);
+
+    #[async_trait]
+    impl TaskHandler for FailingTaskHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            Err(self.0.clone())
+        }
+
+        fn for_task(&self) -> &'static str {
+            "failing"
+        }
+    }
+
+    // 处理时 panic 的处理器
+    struct PanickingTaskHandler;
+
+    #[async_trait]
+    impl TaskHandler for PanickingTaskHandler {
+        async fn handle(&self, _task: &Task) -> Result<(), TaskError> {
+            panic!("handler panicked")
+        }
+
+        fn for_task(&self) -> &'static str {
+            "panicking"
+        }
+    }
+
+    // 每个测试独享的任务获取器, 避免测试之间共享全局队列
+    #[derive(Default)]
+    struct VecFetcher {
+        queue: Mutex<VecDeque<Task>>,
+        // 已投递未确认的任务
+        leased: Mutex<HashMap<String, Task>>,
+        acked: Mutex<Vec<String>>,
+        poll_strategy: PollStrategy,
+        notifier: Option<Arc<Notify>>,
+        // 每次 fetch 实际取走的任务数
+        batches: Mutex<Vec<usize>>,
+    }
+
+    impl VecFetcher {
+        fn with_tasks(tasks: Vec<Task>) -> Arc<Self> {
+            Arc::new(VecFetcher {
+                queue: Mutex::new(tasks.into()),
+                ..Default::default()
+            })
+        }
+
+        async fn push(&self, task: Task) {
+            self.queue.lock().await.push_back(task);
+            if let Some(notifier) = &self.notifier {
+                notifier.notify_one();
+            }
+        }
+
+        async fn len(&self) -> usize {
+            self.queue.lock().await.len()
+        }
+    }
+
+    #[async_trait]
+    impl Fetcher for VecFetcher {
+        async fn fetch(&self, max: usize) -> Vec<Task> {
+            let mut queue = self.queue.lock().await;
+            let n = max.min(queue.len());
+            if n > 0 {
+                self.batches.lock().await.push(n);
+            }
+            let tasks: Vec<Task> = queue.drain(..n).collect();
+            let mut leased = self.leased.lock().await;
+            for task in &tasks {
+                leased.insert(task.id.clone(), task.clone());
+            }
+            tasks
+        }
+
+        async fn ack(&self, task: &Task) {
+            self.leased.lock().await.remove(&task.id);
+            self.acked.lock().await.push(task.id.clone());
+        }
+
+        async fn nack(&self, task: &Task, _requeue_delay: Duration) {
+            if let Some(task) = self.leased.lock().await.remove(&task.id) {
+                self.queue.lock().await.push_front(task);
+            }
+        }
+
+        fn poll_strategy(&self) -> PollStrategy {
+            self.poll_strategy.clone()
+        }
+
+        fn notifier(&self) -> Option<Arc<Notify>> {
+            self.notifier.clone()
+        }
+    }
+
     #[tokio::test] // 使用 tokio::test 来启用异步测试
     async fn test_worker_processes_tasks_correctly() {
         // 创建任务队列和 Worker
-        let fetcher = Arc::new(LocalQueueFetcher);
-        let mut worker = Worker::new(fetcher);
+        let fetcher = VecFetcher::with_tasks(vec![Task::new("mock".to_string(), json!({}))]);
+        let mut worker = Worker::new(fetcher.clone());
         worker.with_limit(1);
 
         // 添加任务处理器
         worker.add_handler("mock".to_string(), MockTaskHandler);
 
-        // 模拟任务放入队列
-        {
-            let mut queue = worker.task_queue.lock().await;
-            queue.push(Task {
-                task_type: "mock".to_string(),
-                data: json!({}),
-                status: tests::TaskStatus::Queued,
-            });
-        }
-
         // 用 tokio::time::timeout 设置超时时间
-        let result = tokio::time::timeout(std::time::Duration::from_secs(5), worker.run()).await;
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
 
         assert!(result.is_ok(), "The worker run timed out");
+        assert_eq!(fetcher.len().await, 0);
+
+        // 处理完成的任务应被确认
+        assert_eq!(fetcher.acked.lock().await.len(), 1);
+        assert!(fetcher.leased.lock().await.is_empty());
     }
 
     #[tokio::test] // 使用 tokio::test 来启用异步测试
     async fn test_worker_handles_unknown_task_type() {
         // 创建任务队列和 Worker，模拟任务处理
-        let fetcher = Arc::new(LocalQueueFetcher); // 假设 LocalQueueFetcher 实现了 Fetcher
-        let mut worker = Worker::new(fetcher);
-        worker.with_limit(10);
+        let fetcher = VecFetcher::with_tasks(vec![
+            Task::new("mock".to_string(), json!({})),
+            // 未注册的任务类型
+            Task::new("unknown".to_string(), json!({})),
+            Task::new("mock".to_string(), json!({})),
+        ]);
+        let mut worker = Worker::new(fetcher.clone());
+        // 未知类型的任务不计入任务配额
+        worker.with_limit(2);
 
         // 添加 mock 处理器
         worker.add_handler("mock".to_string(), MockTaskHandler);
 
-        // 模拟任务放入队列
-        {
-            let mut queue = worker.task_queue.lock().await;
-            queue.push(Task {
-                task_type: "mock".to_string(),
-                data: json!({}),
-                status: TaskStatus::Queued,
-            });
-
-            // 添加一个未知任务类型
-            queue.push(Task {
-                task_type: "unknown".to_string(), // 未注册的任务类型
-                data: json!({}),
-                status: TaskStatus::Queued,
-            });
-
-            // 添加另一个已知的任务
-            queue.push(Task {
-                task_type: "mock".to_string(),
-                data: json!({}),
-                status: TaskStatus::Queued,
-            });
+        // 用 tokio::time::timeout 设置超时时间
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+
+        assert!(result.is_ok(), "The worker run timed out");
+        assert_eq!(fetcher.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_zero_limit_runs_until_shutdown() {
+        let fetcher = VecFetcher::with_tasks(vec![
+            Task::new("mock".to_string(), json!({})),
+            Task::new("mock".to_string(), json!({})),
+        ]);
+        let mut worker = Worker::new(fetcher.clone());
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+
+        // task_limit 为 0 时不限制任务数, 只能通过停机句柄退出
+        let shutdown = worker.shutdown_handle();
+        let run = tokio::spawn(async move { worker.run().await });
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(
+            !run.is_finished(),
+            "Worker with no limit should keep running"
+        );
+        assert_eq!(fetcher.len().await, 0);
+
+        shutdown.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(5), run).await;
+        assert!(result.is_ok(), "The worker did not stop after shutdown");
+    }
+
+    #[tokio::test]
+    async fn test_worker_shutdown_requeues_in_flight_tasks() {
+        let fetcher = VecFetcher::with_tasks(vec![Task::new("slow".to_string(), json!({}))]);
+        let mut worker = Worker::new(fetcher.clone());
+        worker.with_shutdown_timeout(Duration::from_millis(100));
+        worker.add_handler("slow".to_string(), SlowTaskHandler);
+
+        let shutdown = worker.shutdown_handle();
+        let run = tokio::spawn(async move { worker.run().await });
+
+        // 等待任务被取走并开始执行
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(fetcher.len().await, 0);
+
+        shutdown.cancel();
+        let result = tokio::time::timeout(Duration::from_secs(5), run).await;
+        assert!(
+            result.is_ok(),
+            "The worker did not stop after shutdown timeout"
+        );
+
+        // 超时未完成的任务应被归还, 等待重新投递
+        assert_eq!(fetcher.len().await, 1);
+        assert!(fetcher.acked.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_worker_fetches_in_batches() {
+        let tasks = (0..5)
+            .map(|_| Task::new("mock".to_string(), json!({})))
+            .collect();
+        let fetcher = Arc::new(VecFetcher {
+            queue: Mutex::new(tasks),
+            poll_strategy: PollStrategy {
+                batch_size: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let mut worker = Worker::new(fetcher.clone());
+        worker.concurrency = 0;
+        worker.with_limit(5);
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // 每次最多取走 batch_size 个任务
+        assert_eq!(*fetcher.batches.lock().await, vec![2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_worker_wakes_on_push_notification() {
+        // 空闲间隔很长, 只有推送通知才能让 worker 及时拉取
+        let fetcher = Arc::new(VecFetcher {
+            poll_strategy: PollStrategy {
+                idle_interval: Duration::from_secs(60),
+                max_interval: Duration::from_secs(60),
+                ..Default::default()
+            },
+            notifier: Some(Arc::new(Notify::new())),
+            ..Default::default()
+        });
+        let mut worker = Worker::new(fetcher.clone());
+        worker.with_limit(1);
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+
+        let run = tokio::spawn(async move { worker.run().await });
+
+        // 等待 worker 进入空闲等待
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        fetcher.push(Task::new("mock".to_string(), json!({}))).await;
+
+        let result = tokio::time::timeout(Duration::from_secs(1), run).await;
+        assert!(
+            result.is_ok(),
+            "The worker was not woken by the notification"
+        );
+        assert_eq!(fetcher.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_independent_workers_in_one_process() {
+        let queue_a = LocalQueueHandle::new();
+        let queue_b = LocalQueueHandle::new();
+        queue_a
+            .enqueue_batch(vec![
+                Task::new("mock".to_string(), json!({})),
+                Task::new("mock".to_string(), json!({})),
+            ])
+            .await;
+        queue_b
+            .enqueue(Task::new("mock".to_string(), json!({})))
+            .await;
+
+        let mut worker_a = Worker::new(Arc::new(LocalQueueFetcher::new(queue_a.clone())));
+        worker_a.with_limit(2);
+        worker_a.add_handler("mock".to_string(), MockTaskHandler);
+
+        let mut worker_b = Worker::new(Arc::new(LocalQueueFetcher::new(queue_b.clone())));
+        worker_b.with_limit(1);
+        worker_b.add_handler("mock".to_string(), MockTaskHandler);
+
+        // 两个 worker 各自消费自己的队列
+        let result = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(worker_a.run(), worker_b.run())
+        })
+        .await;
+        assert!(result.is_ok(), "The workers run timed out");
+
+        assert!(queue_a.is_empty().await);
+        assert!(queue_b.is_empty().await);
+        assert_eq!(queue_a.in_flight().await, 0);
+        assert_eq!(queue_b.in_flight().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_dead_letters_fatal_error() {
+        let queue = LocalQueueHandle::new();
+        let task = Task::new("failing".to_string(), json!({"order": 42}));
+        queue.enqueue(task.clone()).await;
+
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.with_limit(1);
+        worker.add_handler(
+            "failing".to_string(),
+            FailingTaskHandler(TaskError::Fatal("bad payload".to_string())),
+        );
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // 不可重试的错误不再投递, 直接进入死信区并保留原始载荷
+        assert!(queue.is_empty().await);
+        let dead = queue.dead_letters().await;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task.id, task.id);
+        assert_eq!(dead[0].task.data, json!({"order": 42}));
+        assert_eq!(
+            dead[0].last_error,
+            TaskError::Fatal("bad payload".to_string())
+        );
+        assert_eq!(dead[0].history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_worker_retries_until_max_attempts() {
+        let queue = LocalQueueHandle::new();
+        let task = Task::new("panicking".to_string(), json!({})).with_max_attempts(3);
+        queue.enqueue(task.clone()).await;
+
+        let mut fetcher = LocalQueueFetcher::new(queue.clone());
+        fetcher.retry_delay = Duration::ZERO;
+        let mut worker = Worker::new(Arc::new(fetcher));
+        worker.with_limit(3);
+        worker.add_handler("panicking".to_string(), PanickingTaskHandler);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // panic 被视为可重试错误, 用尽投递次数后进入死信区
+        let dead = queue.dead_letters().await;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task.attempts, 3);
+        let attempts: Vec<u32> = dead[0].history.iter().map(|r| r.attempt).collect();
+        assert_eq!(attempts, vec![1, 2, 3]);
+        assert!(dead[0].last_error.is_retryable());
+
+        // 重新入队后可以再次投递
+        assert!(queue.requeue_dead_letter(&task.id).await);
+        assert!(queue.dead_letters().await.is_empty());
+        assert_eq!(queue.len().await, 1);
+    }
+
+    // 不支持按类型拉取的 fetcher, 使用 fetch_for 的默认实现
+    struct UnfilteredFetcher(LocalQueueFetcher);
+
+    #[async_trait]
+    impl Fetcher for UnfilteredFetcher {
+        async fn fetch(&self, max: usize) -> Vec<Task> {
+            self.0.fetch(max).await
         }
 
-        // 用 tokio::time::timeout 设置超时时间
-        let result = tokio::time::timeout(std::time::Duration::from_secs(5), worker.run()).await;
+        async fn ack(&self, task: &Task) {
+            self.0.ack(task).await
+        }
+
+        async fn nack(&self, task: &Task, requeue_delay: Duration) {
+            self.0.nack(task, requeue_delay).await
+        }
+
+        async fn fail(&self, task: &Task, error: TaskError) {
+            self.0.fail(task, error).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_only_fetches_known_types_by_default() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue(Task::new("other".to_string(), json!({})))
+            .await;
+        queue
+            .enqueue(Task::new("mock".to_string(), json!({})))
+            .await;
+
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.with_limit(1);
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+        assert_eq!(worker.task_types(), Some(vec!["mock".to_string()]));
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // 共享队列上其他 worker 的任务不会被领取并移入死信区
+        assert_eq!(queue.len().await, 1);
+        assert_eq!(queue.in_flight().await, 0);
+        assert!(queue.dead_letters().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_worker_without_handlers_fetches_nothing() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue(Task::new("mock".to_string(), json!({})))
+            .await;
+
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.with_unknown_task_policy(UnknownTaskPolicy::Requeue);
+        assert_eq!(worker.task_types(), Some(vec![]));
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        worker.fetch(&tx).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(queue.in_flight().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_dead_letters_unknown_task_type_by_default() {
+        let queue = LocalQueueHandle::new();
+        let unknown = Task::new("unknown".to_string(), json!({}));
+        queue.enqueue(unknown.clone()).await;
+        queue
+            .enqueue(Task::new("mock".to_string(), json!({})))
+            .await;
+
+        let mut worker = Worker::new(Arc::new(UnfilteredFetcher(LocalQueueFetcher::new(
+            queue.clone(),
+        ))));
+        worker.with_limit(1);
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        let dead = queue.dead_letters().await;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task.id, unknown.id);
+        assert!(!dead[0].last_error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_worker_requeue_policy_only_fetches_known_types() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue(Task::new("other".to_string(), json!({})))
+            .await;
+        queue
+            .enqueue(Task::new("mock".to_string(), json!({})))
+            .await;
+
+        let mut worker = Worker::new(Arc::new(LocalQueueFetcher::new(queue.clone())));
+        worker.with_limit(1);
+        worker.with_unknown_task_policy(UnknownTaskPolicy::Requeue);
+        worker.add_handler("mock".to_string(), MockTaskHandler);
+        assert_eq!(worker.task_types(), Some(vec!["mock".to_string()]));
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // 其他类型的任务留在队列中, 等待能处理它的 worker
+        assert_eq!(queue.len().await, 1);
+        assert_eq!(queue.in_flight().await, 0);
+        assert!(queue.dead_letters().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_worker_fallback_handler_processes_unknown_types() {
+        let fetcher = VecFetcher::with_tasks(vec![
+            Task::new("unknown".to_string(), json!({})),
+            Task::new("another".to_string(), json!({})),
+        ]);
+        let mut worker = Worker::new(fetcher.clone());
+        worker.with_limit(2);
+        worker.with_fallback_handler(MockTaskHandler);
+        assert_eq!(worker.task_types(), None);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), worker.run()).await;
+        assert!(result.is_ok(), "The worker run timed out");
+
+        // 兜底处理器处理成功的任务同样被确认
+        assert_eq!(fetcher.len().await, 0);
+        assert_eq!(fetcher.acked.lock().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_worker_extends_lease_of_long_running_task() {
+        let queue = LocalQueueHandle::new();
+        queue
+            .enqueue(Task::new("delay".to_string(), json!({})))
+            .await;
+
+        let mut fetcher = LocalQueueFetcher::new(queue.clone());
+        fetcher.visibility_timeout = Duration::from_millis(50);
+        let mut worker = Worker::new(Arc::new(fetcher));
+        worker.with_limit(1);
+        worker.with_heartbeat_interval(Duration::from_millis(10));
+        worker.add_handler(
+            "delay".to_string(),
+            DelayTaskHandler(Duration::from_millis(200)),
+        );
+        let run = tokio::spawn(async move { worker.run().await });
+
+        // 处理时间超过可见性超时, 续租后任务不会被其他 worker 领取
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let other = LocalQueueFetcher::new(queue.clone());
+        assert!(other.fetch(10).await.is_empty());
 
+        let result = tokio::time::timeout(Duration::from_secs(5), run).await;
         assert!(result.is_ok(), "The worker run timed out");
+        assert_eq!(queue.len().await, 0);
+        assert_eq!(queue.in_flight().await, 0);
     }
 }